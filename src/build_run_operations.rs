@@ -1,10 +1,14 @@
 use std::process::Command;
+use crate::process;
 
-pub struct BuildRunOperations;
+pub struct BuildRunOperations {
+    /// Python interpreter to run manage.py with, e.g. "python3"
+    python: &'static str,
+}
 
 impl BuildRunOperations {
-    pub fn new() -> Self {
-        BuildRunOperations
+    pub fn new(python: &'static str) -> Self {
+        BuildRunOperations { python }
     }
 
     pub fn perform_operation(&self) -> Result<(), String> {
@@ -18,11 +22,18 @@ impl BuildRunOperations {
 
     fn start_django_application(&self) -> Result<(), String> {
         // Assuming a Django project in the current directory
-        Command::new("python")
-            .args(&["manage.py", "runserver"])
-            .output()
+        let output = process::output(Command::new(self.python).args(["manage.py", "runserver"]))
             .map_err(|e| format!("Failed to start Django application: {}", e))?;
 
+        if !output.status.success() {
+            return Err(format!(
+                "Django application exited with {}\n{}{}",
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+
         println!("Django application started successfully.");
         Ok(())
     }
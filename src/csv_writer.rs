@@ -1,20 +1,116 @@
+use crate::results::{self, Status};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
 use std::fs::File;
-use std::io::{self, Write, BufWriter};
+use std::io;
+use std::path::Path;
 
+/// One sample of a run, as written to `runs/*.csv`.
+#[derive(Serialize, Deserialize)]
+pub struct ResultRow {
+    #[serde(rename = "Operation")]
+    pub operation: String,
+    #[serde(rename = "Category", default)]
+    pub category: String,
+    /// Files written before the Unit column existed hold milliseconds only
+    #[serde(rename = "Unit", default = "default_unit")]
+    pub unit: String,
+    /// Empty unless the status is ok
+    #[serde(
+        rename = "Value",
+        alias = "Time (ms)",
+        default,
+        serialize_with = "serialize_value",
+        deserialize_with = "csv::invalid_option"
+    )]
+    pub value: Option<f64>,
+    #[serde(rename = "Iteration", default = "default_iteration")]
+    pub iteration: usize,
+    #[serde(rename = "Status", default = "default_status")]
+    pub status: Status,
+    #[serde(rename = "Details", default)]
+    pub details: String,
+    /// Mean CPU utilisation outside the tool while the benchmark ran, in percent
+    #[serde(
+        rename = "Background CPU",
+        default,
+        serialize_with = "serialize_value",
+        deserialize_with = "csv::invalid_option"
+    )]
+    pub background_cpu: Option<f64>,
+    /// Why the background activity counts as excessive; empty when it does not
+    #[serde(rename = "Noise", default)]
+    pub noise: String,
+    /// Mean CPU frequency while the benchmark ran, in MHz
+    #[serde(
+        rename = "CPU MHz",
+        default,
+        serialize_with = "serialize_value",
+        deserialize_with = "csv::invalid_option"
+    )]
+    pub cpu_frequency: Option<f64>,
+    /// Highest CPU temperature while the benchmark ran, in °C
+    #[serde(
+        rename = "Max Temperature",
+        default,
+        serialize_with = "serialize_value",
+        deserialize_with = "csv::invalid_option"
+    )]
+    pub max_temperature: Option<f64>,
+}
+
+/// Average of an operation over all runs of a run type, as written to `avg_<run_type>.csv`.
+#[derive(Serialize, Deserialize)]
+pub struct AverageRow {
+    #[serde(rename = "Operation")]
+    pub operation: String,
+    #[serde(rename = "Category", default)]
+    pub category: String,
+    #[serde(rename = "Average", alias = "Average Time (ms)", serialize_with = "serialize_average")]
+    pub average: f64,
+    #[serde(rename = "Unit", default = "default_unit")]
+    pub unit: String,
+    /// The average in the unit that reads best, e.g. "1.25 s" for 1250 ms
+    #[serde(rename = "Display", default)]
+    pub display: String,
+}
+
+fn default_unit() -> String {
+    "ms".to_string()
+}
+
+fn default_iteration() -> usize {
+    1
+}
+
+fn default_status() -> Status {
+    Status::Ok
+}
+
+fn serialize_value<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.serialize_str(&results::format_value(*value)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_average<S: Serializer>(average: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&results::format_value(*average))
+}
+
+/// Writes rows of one type, with a header taken from the field names.
 pub struct CsvWriter {
-    writer: BufWriter<File>,
+    writer: csv::Writer<File>,
 }
 
 impl CsvWriter {
     pub fn new(file_path: &str) -> io::Result<Self> {
-        let file = File::create(file_path)?;
-        let writer = BufWriter::new(file);
+        let writer = csv::Writer::from_path(file_path)?;
         Ok(CsvWriter { writer })
     }
 
-    pub fn write_row(&mut self, data: &[&str]) -> io::Result<()> {
-        let row = data.join(",") + "\n";
-        self.writer.write_all(row.as_bytes())
+    pub fn write_row<R: Serialize>(&mut self, row: &R) -> io::Result<()> {
+        self.writer.serialize(row).map_err(io::Error::from)
     }
 
     pub fn flush(&mut self) -> io::Result<()> {
@@ -22,3 +118,47 @@ impl CsvWriter {
     }
 }
 
+/// Reads all rows of a file written by `CsvWriter`, matching columns by header.
+pub fn read_rows<R: DeserializeOwned>(file_path: &Path) -> io::Result<Vec<R>> {
+    // Older versions did not quote fields, so details with commas spill into extra columns
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_path(file_path)?;
+    let rows = reader.deserialize().collect::<Result<Vec<R>, csv::Error>>()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_fields_round_trip() {
+        let path = std::env::temp_dir().join(format!("benchinator-csv-{}.csv", std::process::id()));
+        let details = "exit status 1, stderr: \"no such image\"\nsecond line";
+        let mut writer = CsvWriter::new(path.to_str().unwrap()).unwrap();
+        writer
+            .write_row(&ResultRow {
+                operation: "Image Pull".to_string(),
+                category: "docker".to_string(),
+                unit: "ms".to_string(),
+                value: None,
+                iteration: 2,
+                status: Status::Failed,
+                details: details.to_string(),
+                background_cpu: Some(1.5),
+                noise: String::new(),
+                cpu_frequency: None,
+                max_temperature: None,
+            })
+            .unwrap();
+        writer.flush().unwrap();
+
+        let rows = read_rows::<ResultRow>(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].details, details);
+        assert!(rows[0].status == Status::Failed);
+        assert_eq!(rows[0].iteration, 2);
+        assert_eq!(rows[0].value, None);
+        assert_eq!(rows[0].background_cpu, Some(1.5));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::network_operations::Latency;
+use serde::Deserialize;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tokio::task::JoinHandle;
+
+/// How long a direct query waits for its answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Cold and warm lookup latencies of one way of resolving names.
+pub struct ResolverResults {
+    /// First lookup of each hostname
+    pub cold: Latency,
+    /// Repeated lookups of each hostname
+    pub warm: Latency,
+}
+
+pub struct DnsResults {
+    /// Lookups through the operating system's resolver
+    pub system: Result<ResolverResults, String>,
+    /// Queries sent straight to a DNS server, with a description of the server
+    pub direct: Option<(String, Result<ResolverResults, String>)>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    dns: DnsConfig,
+}
+
+#[derive(Deserialize)]
+struct DnsConfig {
+    #[serde(default = "default_hostnames")]
+    hostnames: Vec<String>,
+    /// Lookups per hostname, the first of which counts as cold
+    #[serde(default = "default_iterations")]
+    iterations: usize,
+    /// Answer direct queries from a stub resolver on localhost, which works offline
+    #[serde(default = "default_stub_resolver")]
+    stub_resolver: bool,
+    /// `host:port` of a DNS server to query directly instead of the stub
+    #[serde(default)]
+    server: Option<String>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        DnsConfig {
+            hostnames: default_hostnames(),
+            iterations: default_iterations(),
+            stub_resolver: default_stub_resolver(),
+            server: None,
+        }
+    }
+}
+
+fn default_hostnames() -> Vec<String> {
+    vec!["localhost".to_string(), "github.com".to_string(), "pypi.org".to_string()]
+}
+
+fn default_iterations() -> usize {
+    20
+}
+
+fn default_stub_resolver() -> bool {
+    true
+}
+
+pub struct DnsOperations {
+    config: DnsConfig,
+}
+
+impl DnsOperations {
+    pub fn new() -> Result<Self, String> {
+        let config_str = std::fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        if config.dns.hostnames.is_empty() {
+            return Err("No hostnames configured in [dns]".to_string());
+        }
+        Ok(DnsOperations { config: config.dns })
+    }
+
+    pub async fn perform_operation(&self) -> Result<DnsResults, String> {
+        println!("Performing DNS operations...");
+
+        println!("Resolving through the system resolver...");
+        let system = self.measure(|hostname| async move {
+            tokio::net::lookup_host((hostname.as_str(), 0))
+                .await
+                .map_err(|e| format!("Failed to resolve {}: {}", hostname, e))?
+                .next()
+                .map(|_| ())
+                .ok_or_else(|| format!("{} did not resolve to any address", hostname))
+        })
+        .await;
+
+        let (stub, server) = match &self.config.server {
+            Some(server) => (None, Some(server.clone())),
+            None if self.config.stub_resolver => {
+                let stub = StubResolver::start().await?;
+                let address = stub.address.clone();
+                (Some(stub), Some(address))
+            }
+            None => (None, None),
+        };
+        let direct = match server {
+            Some(server) => {
+                let description = match stub {
+                    Some(_) => format!("the stub resolver on {}", server),
+                    None => format!("server {}", server),
+                };
+                println!("Querying {} directly...", description);
+                let results = self
+                    .measure(|hostname| {
+                        let server = server.clone();
+                        async move { query(&server, &hostname).await }
+                    })
+                    .await;
+                Some((description, results))
+            }
+            None => None,
+        };
+        drop(stub);
+
+        println!("DNS operations completed.");
+        Ok(DnsResults { system, direct })
+    }
+
+    /// Resolves every hostname `iterations` times with `lookup`.
+    async fn measure<F, Fut>(&self, lookup: F) -> Result<ResolverResults, String>
+    where
+        F: Fn(String) -> Fut,
+        Fut: std::future::Future<Output = Result<(), String>>,
+    {
+        let mut cold = Vec::new();
+        let mut warm = Vec::new();
+        for hostname in &self.config.hostnames {
+            for i in 0..self.config.iterations.max(2) {
+                let start = Instant::now();
+                lookup(hostname.clone()).await?;
+                if i == 0 {
+                    cold.push(start.elapsed());
+                } else {
+                    warm.push(start.elapsed());
+                }
+            }
+        }
+        Ok(ResolverResults {
+            cold: Latency::of(cold),
+            warm: Latency::of(warm),
+        })
+    }
+}
+
+/// Sends an A query for `hostname` to `server` and waits for a successful answer.
+async fn query(server: &str, hostname: &str) -> Result<(), String> {
+    let id = rand::random::<u16>();
+    let request = a_query(id, hostname)?;
+    let address = tokio::net::lookup_host(server)
+        .await
+        .map_err(|e| format!("Failed to resolve DNS server {}: {}", server, e))?
+        .next()
+        .ok_or_else(|| format!("DNS server {} did not resolve to any address", server))?;
+    let socket = UdpSocket::bind(unspecified(&address))
+        .await
+        .map_err(|e| format!("Failed to open UDP socket: {}", e))?;
+    socket
+        .connect(address)
+        .await
+        .map_err(|e| format!("Failed to reach DNS server {}: {}", server, e))?;
+
+    socket
+        .send(&request)
+        .await
+        .map_err(|e| format!("Failed to query {}: {}", server, e))?;
+
+    let mut response = [0u8; 512];
+    loop {
+        let len = tokio::time::timeout(QUERY_TIMEOUT, socket.recv(&mut response))
+            .await
+            .map_err(|_| format!("{} did not answer the query for {}", server, hostname))?
+            .map_err(|e| format!("Failed to read answer from {}: {}", server, e))?;
+        // Ignore stray datagrams that do not answer this query
+        if len < 12 || response[..2] != id.to_be_bytes() {
+            continue;
+        }
+        return match response[3] & 0x0f {
+            0 => Ok(()),
+            3 => Err(format!("{} does not exist according to {}", hostname, server)),
+            rcode => Err(format!("{} answered the query for {} with error {}", server, hostname, rcode)),
+        };
+    }
+}
+
+/// Local address to send from: any address of the server's family.
+fn unspecified(server: &SocketAddr) -> SocketAddr {
+    match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
+
+/// Builds an A query for `hostname` with recursion desired.
+fn a_query(id: u16, hostname: &str) -> Result<Vec<u8>, String> {
+    let mut request = Vec::with_capacity(hostname.len() + 18);
+    request.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    request.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in hostname.trim_end_matches('.').split('.') {
+        // The length byte leaves 6 bits for the length; longer ones mean compression
+        if label.is_empty() || label.len() > 63 {
+            return Err(format!(
+                "Cannot query {}: labels must be 1 to 63 bytes long",
+                hostname
+            ));
+        }
+        request.push(label.len() as u8);
+        request.extend_from_slice(label.as_bytes());
+    }
+    // Root label, type A, class IN
+    request.extend_from_slice(&[0, 0, 1, 0, 1]);
+    Ok(request)
+}
+
+/// DNS server on localhost that answers every A query with 127.0.0.1. It stops when dropped.
+struct StubResolver {
+    address: String,
+    task: JoinHandle<()>,
+}
+
+impl StubResolver {
+    async fn start() -> Result<Self, String> {
+        Self::start_on("127.0.0.1:0").await
+    }
+
+    async fn start_on(bind: &str) -> Result<Self, String> {
+        let socket = UdpSocket::bind(bind)
+            .await
+            .map_err(|e| format!("Failed to start stub resolver: {}", e))?;
+        let address = socket
+            .local_addr()
+            .map_err(|e| format!("Failed to start stub resolver: {}", e))?
+            .to_string();
+        let task = tokio::spawn(async move {
+            let mut request = [0u8; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut request).await {
+                if let Some(response) = answer(&request[..len]) {
+                    let _ = socket.send_to(&response, peer).await;
+                }
+            }
+        });
+        Ok(StubResolver { address, task })
+    }
+}
+
+impl Drop for StubResolver {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Builds the stub's response to a single-question query.
+fn answer(request: &[u8]) -> Option<Vec<u8>> {
+    if request.len() < 12 || request[4..6] != [0, 1] {
+        return None;
+    }
+
+    // The question runs from the header to the end of the name, plus type and class
+    let mut end = 12;
+    while *request.get(end)? != 0 {
+        end += 1 + request[end] as usize;
+    }
+    end += 5;
+    let question = request.get(12..end)?;
+    let is_a = question[question.len() - 4..question.len() - 2] == [0, 1];
+
+    let mut response = Vec::with_capacity(end + 16);
+    response.extend_from_slice(&request[..2]);
+    // Response, recursion desired and available, no error
+    response.extend_from_slice(&[0x81, 0x80, 0, 1, 0, is_a as u8, 0, 0, 0, 0]);
+    response.extend_from_slice(question);
+    if is_a {
+        // Name pointer to the question, type A, class IN, TTL 0, 127.0.0.1
+        response.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0, 0, 0, 4, 127, 0, 0, 1]);
+    }
+    Some(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_a_queries_with_localhost() {
+        let request = a_query(0x1234, "example.com.").unwrap();
+        let response = answer(&request).unwrap();
+        assert_eq!(&response[..2], &[0x12, 0x34]);
+        // Response without error, one question and one answer
+        assert_eq!(&response[2..8], &[0x81, 0x80, 0, 1, 0, 1]);
+        assert_eq!(&response[12..request.len()], &request[12..]);
+        assert_eq!(&response[response.len() - 4..], &[127, 0, 0, 1]);
+    }
+
+    #[test]
+    fn answers_other_types_without_records() {
+        let mut request = a_query(7, "example.com").unwrap();
+        let len = request.len();
+        // AAAA
+        request[len - 3] = 28;
+        let response = answer(&request).unwrap();
+        assert_eq!(&response[6..8], &[0, 0]);
+        assert_eq!(response.len(), request.len());
+    }
+
+    #[test]
+    fn ignores_malformed_queries() {
+        let request = a_query(7, "example.com").unwrap();
+        assert!(answer(&request[..11]).is_none());
+        // Name without its terminating label, and without type and class
+        assert!(answer(&request[..16]).is_none());
+        assert!(answer(&request[..request.len() - 2]).is_none());
+        // Two questions
+        let mut two = request.clone();
+        two[5] = 2;
+        assert!(answer(&two).is_none());
+        // Label length pointing past the end
+        let mut long_label = request;
+        long_label[12] = 200;
+        assert!(answer(&long_label).is_none());
+    }
+
+    #[test]
+    fn rejects_labels_that_do_not_fit() {
+        let longest = "a".repeat(63);
+        let request = a_query(7, &format!("{}.test", longest)).unwrap();
+        assert_eq!(request[12], 63);
+        assert_eq!(&request[13..76], longest.as_bytes());
+
+        for hostname in [format!("{}.test", "a".repeat(64)), "a..test".to_string(), String::new()] {
+            let error = a_query(7, &hostname).unwrap_err();
+            assert_eq!(error, format!("Cannot query {}: labels must be 1 to 63 bytes long", hostname));
+        }
+    }
+
+    #[test]
+    fn sends_from_the_server_address_family() {
+        let v4: SocketAddr = "192.0.2.53:53".parse().unwrap();
+        assert_eq!(unspecified(&v4), "0.0.0.0:0".parse::<SocketAddr>().unwrap());
+        let v6: SocketAddr = "[2001:db8::53]:53".parse().unwrap();
+        assert_eq!(unspecified(&v6), "[::]:0".parse::<SocketAddr>().unwrap());
+    }
+
+    #[tokio::test]
+    async fn query_succeeds_against_the_stub_resolver() {
+        let stub = StubResolver::start().await.unwrap();
+        query(&stub.address, "benchinator.test").await.unwrap();
+        let error = query(&stub.address, &format!("{}.test", "a".repeat(64))).await.unwrap_err();
+        assert!(error.contains("labels must be 1 to 63 bytes long"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn query_reaches_ipv6_servers() {
+        // Hosts without IPv6 loopback cannot run this
+        let Ok(stub) = StubResolver::start_on("[::1]:0").await else {
+            return;
+        };
+        assert!(stub.address.starts_with("[::1]:"));
+        query(&stub.address, "benchinator.test").await.unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use crate::process;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Minimal HTTP/1.1 client for the Docker Engine API (also served by Podman)
+/// on a Unix socket. Any server listening on the socket can stand in for the
+/// engine, which keeps the client usable against a mock.
+pub struct EngineClient {
+    socket: PathBuf,
+    /// Whether requests are registered with `process`, so a timeout can cancel them
+    tracked: bool,
+}
+
+impl EngineClient {
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        EngineClient {
+            socket: socket.into(),
+            tracked: true,
+        }
+    }
+
+    /// A client whose requests are not cancelled, for cleaning up after a cancelled benchmark.
+    pub fn for_cleanup(socket: impl Into<PathBuf>) -> Self {
+        EngineClient {
+            socket: socket.into(),
+            tracked: false,
+        }
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Returns the engine name and version, e.g. ("podman", "4.9.3").
+    pub fn version(&self) -> Result<(String, String), String> {
+        let body = self.expect_json("GET", "/version", None, &[200])?;
+        let version = body["Version"].as_str().unwrap_or("unknown").to_string();
+        let is_podman = body["Components"]
+            .as_array()
+            .is_some_and(|components| {
+                components
+                    .iter()
+                    .any(|c| c["Name"].as_str().is_some_and(|name| name.contains("Podman")))
+            });
+        let name = if is_podman { "podman" } else { "docker" };
+        Ok((name.to_string(), version))
+    }
+
+    pub fn image_exists(&self, image: &str) -> Result<bool, String> {
+        let (status, _) = self.request("GET", &format!("/images/{}/json", encode(image)), None)?;
+        Ok(status == 200)
+    }
+
+    pub fn pull(&self, image: &str) -> Result<(), String> {
+        let path = format!("/images/create?fromImage={}", encode(image));
+        let (status, body) = self.request("POST", &path, None)?;
+        if status != 200 {
+            return Err(error_message(status, &body));
+        }
+
+        // Pull progress is streamed as JSON lines; failures arrive with status 200
+        for line in String::from_utf8_lossy(&body).lines() {
+            if let Ok(progress) = serde_json::from_str::<Value>(line) {
+                if let Some(error) = progress["error"].as_str() {
+                    return Err(format!("Failed to pull {}: {}", image, error));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates a container and returns its id.
+    pub fn create_container(
+        &self,
+        name: &str,
+        image: &str,
+        command: &[String],
+        labels: &[(&str, &str)],
+    ) -> Result<String, String> {
+        let labels: serde_json::Map<String, Value> = labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), json!(value)))
+            .collect();
+        let body = json!({ "Image": image, "Cmd": command, "Labels": labels });
+        let created = self.expect_json(
+            "POST",
+            &format!("/containers/create?name={}", encode(name)),
+            Some(&body),
+            &[201],
+        )?;
+        created["Id"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| "Engine API returned no container id".to_string())
+    }
+
+    pub fn start_container(&self, id: &str) -> Result<(), String> {
+        self.expect("POST", &format!("/containers/{}/start", id), None, &[204, 304])
+    }
+
+    /// Blocks until the container exits and returns its exit code.
+    pub fn wait_container(&self, id: &str) -> Result<i64, String> {
+        let waited = self.expect_json("POST", &format!("/containers/{}/wait", id), None, &[200])?;
+        Ok(waited["StatusCode"].as_i64().unwrap_or(-1))
+    }
+
+    pub fn remove_container(&self, id: &str) -> Result<(), String> {
+        self.expect("DELETE", &format!("/containers/{}?force=true", id), None, &[204, 404])
+    }
+
+    pub fn remove_image(&self, image: &str) -> Result<(), String> {
+        self.expect("DELETE", &format!("/images/{}", encode(image)), None, &[200, 404])
+    }
+
+    fn expect(&self, method: &str, path: &str, body: Option<&Value>, ok: &[u16]) -> Result<(), String> {
+        let (status, response) = self.request(method, path, body)?;
+        if !ok.contains(&status) {
+            return Err(error_message(status, &response));
+        }
+        Ok(())
+    }
+
+    fn expect_json(&self, method: &str, path: &str, body: Option<&Value>, ok: &[u16]) -> Result<Value, String> {
+        let (status, response) = self.request(method, path, body)?;
+        if !ok.contains(&status) {
+            return Err(error_message(status, &response));
+        }
+        serde_json::from_slice(&response)
+            .map_err(|e| format!("Failed to parse Engine API response to {} {}: {}", method, path, e))
+    }
+
+    /// Sends one request on a fresh connection and returns the status code and body.
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<(u16, Vec<u8>), String> {
+        let mut stream = connect(&self.socket)?;
+        let _tracked = if self.tracked {
+            Some(
+                process::track_connection(&stream)
+                    .map_err(|e| format!("Failed to send {} {}: {}", method, path, e))?,
+            )
+        } else {
+            None
+        };
+
+        let payload = body.map(|b| b.to_string()).unwrap_or_default();
+        let mut request = format!("{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n", method, path);
+        if body.is_some() {
+            request.push_str("Content-Type: application/json\r\n");
+        }
+        request.push_str(&format!("Content-Length: {}\r\n\r\n{}", payload.len(), payload));
+
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("Failed to send {} {}: {}", method, path, e))?;
+        let mut response = Vec::new();
+        stream
+            .read_to_end(&mut response)
+            .map_err(|e| format!("Failed to read response to {} {}: {}", method, path, e))?;
+
+        parse_response(&response).ok_or_else(|| format!("Malformed response to {} {}", method, path))
+    }
+}
+
+#[cfg(unix)]
+fn connect(socket: &Path) -> Result<std::os::unix::net::UnixStream, String> {
+    std::os::unix::net::UnixStream::connect(socket)
+        .map_err(|e| format!("Failed to connect to {}: {}", socket.display(), e))
+}
+
+#[cfg(not(unix))]
+fn connect(socket: &Path) -> Result<std::net::TcpStream, String> {
+    Err(format!(
+        "The Engine API backend needs a Unix socket, which is not available for {}",
+        socket.display()
+    ))
+}
+
+fn parse_response(response: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let header_end = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&response[..header_end]);
+    let body = &response[header_end + 4..];
+
+    let status = head.lines().next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let chunked = head.lines().any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+
+    if chunked {
+        Some((status, dechunk(body)?))
+    } else {
+        Some((status, body.to_vec()))
+    }
+}
+
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let size_field = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size_field.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+fn error_message(status: u16, body: &[u8]) -> String {
+    let message = serde_json::from_slice::<Value>(body)
+        .ok()
+        .and_then(|v| v["message"].as_str().map(String::from))
+        .unwrap_or_else(|| String::from_utf8_lossy(body).trim().to_string());
+    format!("Engine API returned {}: {}", status, message)
+}
+
+/// Percent-encodes everything but unreserved characters and the separators of image references.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::io::BufRead;
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+
+    /// Serves one canned response per connection and returns the requests it received.
+    fn mock_engine(name: &str, responses: Vec<String>) -> (EngineClient, JoinHandle<Vec<String>>) {
+        let socket = std::env::temp_dir().join(format!("benchinator-{}-{}.sock", name, std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(length) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str(&String::from_utf8_lossy(&body));
+                requests.push(request);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            requests
+        });
+        (EngineClient::new(socket), server)
+    }
+
+    fn json_response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    fn chunked_response(chunks: &[&str]) -> String {
+        let mut response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_string();
+        for chunk in chunks {
+            response.push_str(&format!("{:x}\r\n{}\r\n", chunk.len(), chunk));
+        }
+        response + "0\r\n\r\n"
+    }
+
+    #[test]
+    fn version_detects_podman() {
+        let body = r#"{"Version":"4.9.3","Components":[{"Name":"Podman Engine"}]}"#;
+        let (engine, server) = mock_engine("version", vec![json_response("200 OK", body)]);
+        assert_eq!(engine.version().unwrap(), ("podman".to_string(), "4.9.3".to_string()));
+        assert!(server.join().unwrap()[0].starts_with("GET /version HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn create_container_sends_image_and_labels() {
+        let (engine, server) = mock_engine("create", vec![json_response("201 Created", r#"{"Id":"abc123"}"#)]);
+        let command = vec!["true".to_string()];
+        let id = engine
+            .create_container("bench one", "alpine:3", &command, &[("benchinator.run", "42")])
+            .unwrap();
+        assert_eq!(id, "abc123");
+
+        let request = &server.join().unwrap()[0];
+        assert!(request.starts_with("POST /containers/create?name=bench%20one HTTP/1.1\r\n"));
+        assert!(request.contains(r#""Image":"alpine:3""#));
+        assert!(request.contains(r#""benchinator.run":"42""#));
+    }
+
+    #[test]
+    fn pull_fails_on_error_line_in_chunked_stream() {
+        let response = chunked_response(&[
+            "{\"status\":\"Pulling from library/alpine\"}\n",
+            "{\"error\":\"manifest unknown\"}\n",
+        ]);
+        let (engine, server) = mock_engine("pull", vec![response]);
+        let error = engine.pull("alpine:missing").unwrap_err();
+        assert!(error.contains("manifest unknown"), "{}", error);
+        assert!(server.join().unwrap()[0].starts_with("POST /images/create?fromImage=alpine:missing "));
+    }
+
+    #[test]
+    fn wait_returns_non_zero_status_code() {
+        let (engine, server) = mock_engine("wait", vec![json_response("200 OK", r#"{"StatusCode":3}"#)]);
+        assert_eq!(engine.wait_container("abc123").unwrap(), 3);
+        assert!(server.join().unwrap()[0].starts_with("POST /containers/abc123/wait "));
+    }
+
+    #[test]
+    fn dechunk_joins_chunks_and_ignores_extensions() {
+        let body = b"4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n";
+        assert_eq!(dechunk(body).unwrap(), b"Wikipedia");
+    }
+
+    #[test]
+    fn dechunk_rejects_truncated_body() {
+        assert_eq!(dechunk(b"a\r\nshort\r\n"), None);
+        assert_eq!(dechunk(b"zz\r\n"), None);
+    }
+}
@@ -1,103 +1,735 @@
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use crate::process;
 use std::fs;
-use toml::Value;
+use std::io::Read;
+use std::net::{SocketAddr, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+use crate::docker_api::EngineClient;
+use crate::prerequisites::parse_version;
+use crate::download_operations::megabytes_per_second;
+use crate::units;
+
+/// Container runtimes with a Docker-compatible CLI, in detection order.
+pub const RUNTIMES: &[&str] = &["docker", "podman", "nerdctl"];
+
+/// Containers created through the Engine API and not removed yet, with their
+/// socket, so they can be removed when the benchmark is cancelled.
+static API_CONTAINERS: Mutex<Vec<(PathBuf, String)>> = Mutex::new(Vec::new());
+
+/// Whether the test image and the registry image were present before the
+/// benchmark, so cleanup keeps them.
+#[derive(Clone, Copy)]
+struct Preexisting {
+    image: bool,
+    registry: bool,
+}
+
+const BUILD_DIR: &str = "artifacts/docker_build";
+const MOUNT_DIR: &str = "artifacts/docker_mount";
+
+#[derive(Deserialize)]
+struct Config {
+    docker: DockerConfig,
+}
+
+#[derive(Deserialize)]
+struct DockerConfig {
+    #[serde(default = "default_runtime")]
+    runtime: String,
+    #[serde(default = "default_backend")]
+    backend: String,
+    #[serde(default)]
+    socket: Option<String>,
+    image: String,
+    test_command: Vec<String>,
+    #[serde(default = "default_exec_iterations")]
+    exec_iterations: u32,
+    #[serde(default = "default_mount_file_mb")]
+    mount_file_mb: u32,
+    #[serde(default)]
+    prune: bool,
+    #[serde(default = "default_pull_source")]
+    pull_source: String,
+    #[serde(default)]
+    image_tarball: Option<String>,
+    #[serde(default = "default_registry_image")]
+    registry_image: String,
+    #[serde(default)]
+    registry_tarball: Option<String>,
+    #[serde(default = "default_registry_port")]
+    registry_port: u16,
+}
+
+fn default_runtime() -> String {
+    "auto".to_string()
+}
+
+fn default_backend() -> String {
+    "cli".to_string()
+}
+
+fn default_pull_source() -> String {
+    "remote".to_string()
+}
+
+fn default_registry_image() -> String {
+    "registry:2".to_string()
+}
+
+fn default_registry_port() -> u16 {
+    5000
+}
+
+fn default_exec_iterations() -> u32 {
+    10
+}
+
+fn default_mount_file_mb() -> u32 {
+    64
+}
+
+pub struct DockerOperationResults {
+    /// Runtime and version that produced the measurements, e.g. "podman 4.9.3"
+    pub runtime: String,
+    /// Where the image was pulled from: remote, tarball or local_registry
+    pub pull_source: String,
+    pub pull_time: Duration,
+    /// Uncompressed size of the pulled image, for the tarball and local_registry
+    /// sources; the registry and a saved tarball differ in compression, so
+    /// neither transfer size is comparable to the other
+    pub pulled_bytes: Option<u64>,
+    /// Pull throughput in MB/s of the uncompressed image size
+    pub pull_throughput: Option<f64>,
+    pub create_time: Duration,
+    pub start_time: Duration,
+    pub exec_time: Duration,
+    pub bind_mount_io_time: Duration,
+    pub build_time: Duration,
+}
+
+/// Container lifecycle timings taken through the Engine API backend.
+pub struct EngineApiResults {
+    /// Engine and version that produced the measurements, e.g. "docker 24.0.7 via Engine API"
+    pub runtime: String,
+    pub pull_time: Duration,
+    pub create_time: Duration,
+    pub start_time: Duration,
+    pub wait_time: Duration,
+    pub remove_time: Duration,
+}
 
 pub struct DockerOperations {
-    config: Value,
+    config: Config,
+    engine: Option<EngineClient>,
+    runtime: String,
+    runtime_version: String,
+    run_id: String,
+    name_prefix: String,
+    label: String,
+    preexisting: Mutex<Option<Preexisting>>,
 }
 
 impl DockerOperations {
-    pub fn new() -> Result<Self, String> {
+    pub fn new(run_id: &str) -> Result<Self, String> {
         let config_str = fs::read_to_string("config.toml")
             .map_err(|e| format!("Failed to read config file: {}", e))?;
-        let config: Value = toml::from_str(&config_str)
+        let config: Config = toml::from_str(&config_str)
             .map_err(|e| format!("Failed to parse config file: {}", e))?;
-        Ok(DockerOperations { config })
-    }
-
-    pub fn perform_operation(&self) -> Result<(), String> {
-        // Clean up any existing images first
-        self.cleanup()?;
-        
-        println!("Performing docker operation...");
-        
-        // Debug print the config
-        println!("Config contents: {:#?}", self.config);
-        
-        // Get image from config
-        let docker_section = self.config.get("docker")
-            .ok_or_else(|| "Docker section not found in config".to_string())?;
-            
-        let image = docker_section.get("image")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| "Docker image not found in config".to_string())?;
-
-        // Pull the image
-        let pull_output = Command::new("docker")
-            .args(&["pull", image])
-            .output()
-            .map_err(|e| format!("Failed to pull docker image: {}", e))?;
-
-        if !pull_output.status.success() {
-            return Err(String::from_utf8_lossy(&pull_output.stderr).into_owned());
-        }
-
-        // Get test command from config
-        let test_command = docker_section.get("test_command")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| "Docker test_command not found in config".to_string())?;
-        
-        let test_args: Vec<&str> = test_command
-            .iter()
-            .map(|v| v.as_str().unwrap_or_default())
-            .collect();
-
-        // Run test with the image
-        let mut docker_args = vec!["run", "--rm", image];
-        docker_args.extend(test_args.iter());
-
-        let run_output = Command::new("docker")
-            .args(&docker_args)
-            .output()
-            .map_err(|e| format!("Failed to run docker container: {}", e))?;
-
-        if !run_output.status.success() {
-            return Err(String::from_utf8_lossy(&run_output.stderr).into_owned());
+        let (engine, runtime, runtime_version) = match config.docker.backend.as_str() {
+            "cli" => {
+                let (runtime, version) = detect_runtime(&config.docker.runtime)?;
+                (None, runtime, version)
+            }
+            "api" => {
+                let socket = match &config.docker.socket {
+                    Some(socket) => socket.clone(),
+                    None => default_socket(&config.docker.runtime),
+                };
+                let engine = EngineClient::new(socket);
+                let (runtime, version) = engine.version()?;
+                (Some(engine), runtime, version)
+            }
+            other => return Err(format!("Unknown docker backend '{}', expected cli or api", other)),
+        };
+        println!(
+            "Using container runtime {} {} ({} backend)",
+            runtime, runtime_version, config.docker.backend
+        );
+        Ok(DockerOperations {
+            config,
+            engine,
+            runtime,
+            runtime_version,
+            run_id: run_id.to_string(),
+            name_prefix: format!("benchinator-{}", run_id),
+            label: run_label(run_id),
+            preexisting: Mutex::new(None),
+        })
+    }
+
+    pub fn perform_operation(&self) -> Result<DockerOperationResults, String> {
+        let image = &self.config.docker.image;
+        if self.config.docker.prune {
+            // Opt-in: start from an empty image cache, at the cost of all unused Docker data
+            self.prune()?;
+        }
+
+        // An image that was already there belongs to the user and is kept
+        let image_existed = self.docker(&["image", "inspect", image]).is_ok();
+        if image_existed {
+            println!("Image {} is already present; the pull measures a cached pull", image);
+        }
+
+        let local_registry = self.config.docker.pull_source == "local_registry";
+        let registry_existed =
+            local_registry && self.docker(&["image", "inspect", &self.config.docker.registry_image]).is_ok();
+        *self.preexisting.lock().unwrap() = Some(Preexisting {
+            image: image_existed,
+            registry: registry_existed,
+        });
+
+        println!("Performing docker operations...");
+        let results = self.run_phases(image_existed);
+
+        // Cleanup after test, also when a phase failed. A cancelled benchmark
+        // is cleaned up by the caller, once its processes have been killed
+        if process::cancelled() {
+            return results;
+        }
+        self.cleanup();
+        if self.config.docker.prune {
+            self.prune()?;
+        }
+
+        results
+    }
+
+    /// Removes the containers and images of this run, and the images it
+    /// pulled. Also works after the benchmark was cancelled.
+    pub fn cleanup(&self) {
+        println!("Cleaning up {} resources of run {}...", self.runtime, self.run_id);
+        Self::cleanup_run(&self.runtime, &self.run_id);
+        // Nothing was pulled before the images were checked
+        let Some(preexisting) = *self.preexisting.lock().unwrap() else {
+            return;
+        };
+        let rmi = |image: &str| {
+            let _ = process::cleanup_output(Command::new(&self.runtime).args(["rmi", image]));
+        };
+        if self.config.docker.pull_source == "local_registry" {
+            rmi(&self.local_registry_image());
+            if !preexisting.registry {
+                rmi(&self.config.docker.registry_image);
+            }
+        }
+        if !preexisting.image {
+            rmi(&self.config.docker.image);
+        }
+    }
+
+    pub fn uses_engine_api(&self) -> bool {
+        self.engine.is_some()
+    }
+
+    /// Times pull, create, start, run and remove through the Engine API, so
+    /// the measurements contain no CLI start-up.
+    pub fn perform_api_operation(&self) -> Result<EngineApiResults, String> {
+        let engine = self
+            .engine
+            .as_ref()
+            .ok_or_else(|| "Engine API backend is not configured".to_string())?;
+        let docker = &self.config.docker;
+        if docker.pull_source != "remote" {
+            return Err(format!(
+                "pull_source = \"{}\" is only supported by the cli backend",
+                docker.pull_source
+            ));
+        }
+
+        println!("Performing docker operations through the Engine API...");
+        let image_existed = engine.image_exists(&docker.image)?;
+        if image_existed {
+            println!("Image {} is already present; the pull measures a cached pull", docker.image);
+        }
+
+        let pull_start = Instant::now();
+        engine.pull(&docker.image)?;
+        let pull_time = pull_start.elapsed();
+        println!("Image pull completed in {}", units::display_duration(pull_time));
+
+        let name = format!("{}-api", self.name_prefix);
+        let create_start = Instant::now();
+        let id = engine.create_container(
+            &name,
+            &docker.image,
+            &docker.test_command,
+            &[("benchinator.run", &self.run_id)],
+        )?;
+        let create_time = create_start.elapsed();
+        API_CONTAINERS.lock().unwrap().push((engine.socket().to_path_buf(), id.clone()));
+        println!("Container create completed in {}", units::display_duration(create_time));
+
+        let run = || -> Result<(Duration, Duration), String> {
+            let start_start = Instant::now();
+            engine.start_container(&id)?;
+            let start_time = start_start.elapsed();
+            println!("Container start completed in {}", units::display_duration(start_time));
+
+            let wait_start = Instant::now();
+            let exit_code = engine.wait_container(&id)?;
+            let wait_time = wait_start.elapsed();
+            if exit_code != 0 {
+                return Err(format!("Container exited with status {}", exit_code));
+            }
+            println!("Container run until exit completed in {}", units::display_duration(wait_time));
+            Ok((start_time, wait_time))
+        };
+        let run_result = run();
+
+        // Cleanup after test, also when the run failed
+        let remove_start = Instant::now();
+        let removed = engine.remove_container(&id);
+        let remove_time = remove_start.elapsed();
+        if removed.is_ok() {
+            API_CONTAINERS.lock().unwrap().retain(|(_, created)| *created != id);
+        }
+        if !image_existed {
+            let _ = engine.remove_image(&docker.image);
+        }
+
+        let (start_time, wait_time) = run_result?;
+        removed?;
+        println!("Container remove completed in {}", units::display_duration(remove_time));
+
+        Ok(EngineApiResults {
+            runtime: format!("{} {} via Engine API", self.runtime, self.runtime_version),
+            pull_time,
+            create_time,
+            start_time,
+            wait_time,
+            remove_time,
+        })
+    }
+
+    /// Removes the containers that a cancelled Engine API benchmark left behind.
+    pub fn cleanup_api_containers() {
+        let containers = std::mem::take(&mut *API_CONTAINERS.lock().unwrap());
+        for (socket, id) in containers {
+            println!("Removing container {} created through the Engine API...", id);
+            if let Err(e) = EngineClient::for_cleanup(socket).remove_container(&id) {
+                eprintln!("Failed to remove container {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Removes the containers and images labelled with `run_id`, and nothing
+    /// else. Also works after the benchmark was cancelled.
+    pub fn cleanup_run(runtime: &str, run_id: &str) {
+        let filter = format!("label={}", run_label(run_id));
+
+        if let Some(containers) = list_ids(runtime, &["ps", "-aq", "--filter", &filter]) {
+            let mut args = vec!["rm", "-f"];
+            args.extend(containers.iter().map(String::as_str));
+            let _ = process::cleanup_output(Command::new(runtime).args(&args));
+        }
+
+        if let Some(images) = list_ids(runtime, &["images", "-q", "--filter", &filter]) {
+            let mut args = vec!["rmi", "-f"];
+            args.extend(images.iter().map(String::as_str));
+            let _ = process::cleanup_output(Command::new(runtime).args(&args));
+        }
+    }
+
+    fn run_phases(&self, image_existed: bool) -> Result<DockerOperationResults, String> {
+        let docker = &self.config.docker;
+
+        // Pull the image, or load it when benchmarking offline
+        let (image, pull_time, pulled_bytes) = match docker.pull_source.as_str() {
+            "remote" => {
+                let pull_start = Instant::now();
+                self.docker(&["pull", &docker.image])?;
+                (docker.image.clone(), pull_start.elapsed(), None)
+            }
+            "tarball" => {
+                let tarball = docker
+                    .image_tarball
+                    .as_deref()
+                    .ok_or_else(|| "pull_source = \"tarball\" needs image_tarball".to_string())?;
+                if !Path::new(tarball).exists() {
+                    return Err(format!("Image tarball '{}' does not exist", tarball));
+                }
+                let load_start = Instant::now();
+                self.docker(&["load", "-i", tarball])?;
+                let load_time = load_start.elapsed();
+                (docker.image.clone(), load_time, Some(self.image_size(&docker.image)?))
+            }
+            "local_registry" => {
+                let local_image = self.push_to_local_registry(image_existed)?;
+                let mut pull_args = vec!["pull"];
+                pull_args.extend(self.insecure_registry_args());
+                pull_args.push(&local_image);
+                let pull_start = Instant::now();
+                self.docker(&pull_args)?;
+                let pull_time = pull_start.elapsed();
+                (local_image.clone(), pull_time, Some(self.image_size(&local_image)?))
+            }
+            other => {
+                return Err(format!(
+                    "Unknown pull_source '{}', expected remote, tarball or local_registry",
+                    other
+                ))
+            }
+        };
+        let pull_throughput = pulled_bytes
+            .filter(|_| !pull_time.is_zero())
+            .map(|bytes| megabytes_per_second(bytes, pull_time));
+        println!("Image pull from {} completed in {}", docker.pull_source, units::display_duration(pull_time));
+        if let Some(bytes) = pulled_bytes {
+            println!("Pulled image size {} (uncompressed)", units::display(bytes as f64, "bytes"));
         }
 
+        // Create a container running the test command
+        let container = format!("{}-start", self.name_prefix);
+        let mut create_args = vec!["create", "--name", &container, "--label", &self.label, &image];
+        create_args.extend(docker.test_command.iter().map(String::as_str));
+        let create_start = Instant::now();
+        self.docker(&create_args)?;
+        let create_time = create_start.elapsed();
+        println!("Container create completed in {}", units::display_duration(create_time));
+
+        // Cold start until the test command prints something
+        let start_time = self.time_to_first_output(&container)?;
+        println!("Container start to first output completed in {}", units::display_duration(start_time));
+
+        // Long-running container for exec and bind mount measurements
+        fs::create_dir_all(MOUNT_DIR)
+            .map_err(|e| format!("Failed to create mount directory: {}", e))?;
+        let mount_dir = fs::canonicalize(MOUNT_DIR)
+            .map_err(|e| format!("Failed to resolve mount directory: {}", e))?;
+        let mount = format!("{}:/mnt/benchmark", mount_dir.display());
+        let container = format!("{}-exec", self.name_prefix);
+        self.docker(&[
+            "run", "-d", "--name", &container, "--label", &self.label, "-v", &mount,
+            &image, "tail", "-f", "/dev/null",
+        ])?;
+
+        let iterations = docker.exec_iterations.max(1);
+        let exec_start = Instant::now();
+        for _ in 0..iterations {
+            self.docker(&["exec", &container, "true"])?;
+        }
+        let exec_time = exec_start.elapsed() / iterations;
+        println!("Container exec completed in {} on average", units::display_duration(exec_time));
+
+        let io_script = format!(
+            "dd if=/dev/zero of=/mnt/benchmark/io.bin bs=1048576 count={} && \
+             cat /mnt/benchmark/io.bin > /dev/null && rm /mnt/benchmark/io.bin",
+            docker.mount_file_mb
+        );
+        let io_start = Instant::now();
+        self.docker(&["exec", &container, "sh", "-c", &io_script])?;
+        let bind_mount_io_time = io_start.elapsed();
+        println!("Bind mount file I/O completed in {}", units::display_duration(bind_mount_io_time));
+
+        // Build a small image on top of the pulled one
+        let build_time = self.build_image(&image)?;
+        println!("Image build completed in {}", units::display_duration(build_time));
+
         println!("Docker test completed successfully");
-        
-        // Cleanup after test
-        self.cleanup()?;
-        
-        Ok(())
+        Ok(DockerOperationResults {
+            runtime: format!("{} {}", self.runtime, self.runtime_version),
+            pull_source: docker.pull_source.clone(),
+            pull_time,
+            pulled_bytes,
+            pull_throughput,
+            create_time,
+            start_time,
+            exec_time,
+            bind_mount_io_time,
+            build_time,
+        })
+    }
+
+    fn time_to_first_output(&self, container: &str) -> Result<Duration, String> {
+        let start = Instant::now();
+        let mut child = process::spawn(
+            Command::new(&self.runtime)
+                .args(["start", "-a", container])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .map_err(|e| format!("Failed to start docker container: {}", e))?;
+
+        // Stops at the first byte, or at exit when the command prints nothing
+        let mut first_byte = [0u8; 1];
+        if let Some(stdout) = child.child_mut().stdout.as_mut() {
+            let _ = stdout.read(&mut first_byte);
+        }
+        let start_time = start.elapsed();
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to wait for docker container: {}", e))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+
+        Ok(start_time)
     }
 
-    fn cleanup(&self) -> Result<(), String> {
-        println!("Cleaning up Docker resources...");
-        
-        // Get image from config
-        let docker_section = self.config.get("docker")
-            .ok_or_else(|| "Docker section not found in config".to_string())?;
-            
-        let image = docker_section.get("image")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| "Docker image not found in config".to_string())?;
+    /// Starts a registry container on localhost and pushes the test image to
+    /// it, removing local copies so the following pull transfers every layer.
+    /// Returns the image reference to pull.
+    fn push_to_local_registry(&self, image_existed: bool) -> Result<String, String> {
+        let docker = &self.config.docker;
+        if let Some(tarball) = &docker.registry_tarball {
+            self.docker(&["load", "-i", tarball])?;
+        }
+        if let Some(tarball) = &docker.image_tarball {
+            self.docker(&["load", "-i", tarball])?;
+        } else if self.docker(&["image", "inspect", &docker.image]).is_err() {
+            // Not part of the measurement; the image is removed again after the run
+            println!("Image {} is not present locally, pulling it before pushing it to the local registry", docker.image);
+            self.docker(&["pull", &docker.image]).map_err(|e| {
+                format!(
+                    "Image {} is not present locally and could not be pulled; set image_tarball or pull it first: {}",
+                    docker.image,
+                    e.trim()
+                )
+            })?;
+        }
+
+        let registry = format!("{}-registry", self.name_prefix);
+        let publish = format!("127.0.0.1:{}:5000", docker.registry_port);
+        self.docker(&[
+            "run", "-d", "--name", &registry, "--label", &self.label, "-p", &publish,
+            &docker.registry_image,
+        ])?;
+        wait_for_port(docker.registry_port, Duration::from_secs(30))?;
+
+        let local_image = self.local_registry_image();
+        self.docker(&["tag", &docker.image, &local_image])?;
+        let mut push_args = vec!["push"];
+        push_args.extend(self.insecure_registry_args());
+        push_args.push(&local_image);
+        self.docker(&push_args)?;
+
+        self.docker(&["rmi", &local_image])?;
+        if image_existed {
+            println!("Layers shared with the existing image {} are not transferred again", docker.image);
+        } else {
+            self.docker(&["rmi", &docker.image])?;
+        }
+
+        Ok(local_image)
+    }
+
+    fn local_registry_image(&self) -> String {
+        let name = self.config.docker.image.rsplit('/').next().unwrap_or_default();
+        let name = name.split('@').next().unwrap_or_default();
+        format!("localhost:{}/benchinator/{}", self.config.docker.registry_port, name)
+    }
+
+    /// Flags that allow plain HTTP to the local registry; Docker allows it for localhost by default.
+    fn insecure_registry_args(&self) -> Vec<&'static str> {
+        match self.runtime.as_str() {
+            "podman" => vec!["--tls-verify=false"],
+            "nerdctl" => vec!["--insecure-registry"],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Uncompressed size of a local image in bytes.
+    fn image_size(&self, image: &str) -> Result<u64, String> {
+        let output = self.docker(&["image", "inspect", "--format", "{{.Size}}", image])?;
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse()
+            .map_err(|e| format!("Failed to read size of image {}: {}", image, e))
+    }
+
+    fn build_image(&self, image: &str) -> Result<Duration, String> {
+        fs::create_dir_all(BUILD_DIR)
+            .map_err(|e| format!("Failed to create build directory: {}", e))?;
+        let dockerfile = format!("FROM {}\nRUN echo benchinator > /benchinator.txt\n", image);
+        fs::write(Path::new(BUILD_DIR).join("Dockerfile"), dockerfile)
+            .map_err(|e| format!("Failed to write Dockerfile: {}", e))?;
+
+        let tag = format!("{}-build", self.name_prefix);
+        let build_start = Instant::now();
+        self.docker(&["build", "--no-cache", "--label", &self.label, "-t", &tag, BUILD_DIR])?;
+        Ok(build_start.elapsed())
+    }
+
+    fn docker(&self, args: &[&str]) -> Result<Output, String> {
+        let output = process::output(Command::new(&self.runtime).args(args))
+            .map_err(|e| format!("Failed to run {} {}: {}", self.runtime, args[0], e))?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+
+        Ok(output)
+    }
+
+    fn prune(&self) -> Result<(), String> {
+        println!("Pruning Docker resources (docker.prune = true)...");
 
         // Remove the specific image if it exists
-        Command::new("docker")
-            .args(&["rmi", "-f", image])
-            .output()
+        process::output(Command::new(&self.runtime).args(["rmi", "-f", &self.config.docker.image]))
             .map_err(|e| format!("Failed to remove docker image: {}", e))?;
 
         // Additional cleanup with system prune
-        Command::new("docker")
-            .args(&["system", "prune", "-f"])
-            .output()
+        process::output(Command::new(&self.runtime).args(["system", "prune", "-f"]))
             .map_err(|e| format!("Failed to prune docker system: {}", e))?;
 
-        println!("Docker cleanup completed");
+        println!("Docker prune completed");
         Ok(())
     }
 }
 
+fn run_label(run_id: &str) -> String {
+    format!("benchinator.run={}", run_id)
+}
+
+/// Resolves the configured runtime ("auto" or a runtime name) to a binary and
+/// its version. "auto" prefers the first runtime whose daemon or service answers.
+fn detect_runtime(configured: &str) -> Result<(String, String), String> {
+    if configured != "auto" {
+        let version = runtime_version(configured)
+            .ok_or_else(|| format!("Container runtime '{}' is not installed", configured))?;
+        return Ok((configured.to_string(), version));
+    }
+
+    let installed: Vec<(&str, String)> = RUNTIMES
+        .iter()
+        .filter_map(|&runtime| runtime_version(runtime).map(|version| (runtime, version)))
+        .collect();
+
+    installed
+        .iter()
+        .find(|(runtime, _)| {
+            process::output(Command::new(runtime).arg("info"))
+                .map(|output| output.status.success())
+                .unwrap_or(false)
+        })
+        .or_else(|| installed.first())
+        .map(|(runtime, version)| (runtime.to_string(), version.clone()))
+        .ok_or_else(|| format!("No container runtime found (tried {})", RUNTIMES.join(", ")))
+}
+
+fn runtime_version(runtime: &str) -> Option<String> {
+    let output = process::output(Command::new(runtime).arg("--version")).ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let (major, minor, patch) = parse_version(&String::from_utf8_lossy(&output.stdout))?;
+    Some(format!("{}.{}.{}", major, minor, patch))
+}
+
+/// Default Engine API socket of a runtime, honouring `DOCKER_HOST` for Docker.
+pub fn default_socket(runtime: &str) -> String {
+    if runtime == "podman" {
+        if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
+            let socket = Path::new(&runtime_dir).join("podman/podman.sock");
+            if socket.exists() {
+                return socket.display().to_string();
+            }
+        }
+        return "/run/podman/podman.sock".to_string();
+    }
+
+    match std::env::var("DOCKER_HOST") {
+        Ok(host) if host.starts_with("unix://") => host.trim_start_matches("unix://").to_string(),
+        _ => "/var/run/docker.sock".to_string(),
+    }
+}
+
+fn wait_for_port(port: u16, timeout: Duration) -> Result<(), String> {
+    let address = SocketAddr::from(([127, 0, 0, 1], port));
+    let start = Instant::now();
+    while TcpStream::connect_timeout(&address, Duration::from_millis(500)).is_err() {
+        if start.elapsed() > timeout {
+            return Err(format!("Local registry did not start listening on port {}", port));
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+    Ok(())
+}
+
+/// Runs a listing command and returns the ids it printed, if any.
+fn list_ids(runtime: &str, args: &[&str]) -> Option<Vec<String>> {
+    let output = process::cleanup_output(Command::new(runtime).args(args)).ok()?;
+    let ids: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    if ids.is_empty() {
+        None
+    } else {
+        Some(ids)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A runtime that logs its arguments and lists one container and one image.
+    fn fake_runtime(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("benchinator-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("calls.log");
+        let runtime = dir.join("runtime");
+        let script = format!(
+            "#!/bin/sh\necho \"$@\" >> {}\ncase \"$1\" in ps) echo c0ffee;; images) echo beef42;; esac\n",
+            log.display()
+        );
+        fs::write(&runtime, script).unwrap();
+        fs::set_permissions(&runtime, fs::Permissions::from_mode(0o755)).unwrap();
+        // Another test may fork while the script is still open for writing
+        while let Err(e) = Command::new(&runtime).output() {
+            assert_eq!(e.kind(), std::io::ErrorKind::ExecutableFileBusy, "{}", e);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = fs::remove_file(&log);
+        (runtime, log)
+    }
+
+    #[test]
+    fn cleanup_runs_after_cancellation() {
+        let (runtime, log) = fake_runtime("cleanup");
+        let generation = process::reset();
+        let (started, running) = std::sync::mpsc::channel();
+        let (cancel, cancelled) = std::sync::mpsc::channel();
+        let benchmark = std::thread::spawn(move || {
+            process::enter(generation);
+            started.send(()).unwrap();
+            cancelled.recv().unwrap();
+            assert!(process::cancelled());
+            DockerOperations::cleanup_run(runtime.to_str().unwrap(), "run42");
+        });
+        running.recv().unwrap();
+        // The next benchmark starts, leaving this one cancelled
+        process::reset();
+        cancel.send(()).unwrap();
+        benchmark.join().unwrap();
+
+        let calls = fs::read_to_string(&log).unwrap();
+        let calls: Vec<&str> = calls.lines().collect();
+        assert_eq!(
+            calls,
+            [
+                "ps -aq --filter label=benchinator.run=run42",
+                "rm -f c0ffee",
+                "images -q --filter label=benchinator.run=run42",
+                "rmi -f beef42",
+            ]
+        );
+    }
+}
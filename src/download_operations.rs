@@ -1,8 +1,54 @@
-use reqwest;
+use crate::http_client::{Connection, HttpConfig};
+use crate::local_server::{self, LocalServer, LocalServerConfig};
+use crate::process;
+use sha2::{Digest, Sha256};
 use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::task::JoinSet;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Window over which the peak throughput is measured.
+const THROUGHPUT_WINDOW: Duration = Duration::from_millis(250);
+
+/// Results of every download target and of the parallel phase.
+pub struct DownloadResults {
+    /// Operation label of each target, e.g. "Download" or "Download docs", with its results
+    pub targets: Vec<(String, Result<TargetResults, String>)>,
+    pub parallel: Option<Result<ParallelResults, String>>,
+}
+
+/// Connection and transfer metrics of a single download.
+pub struct TargetResults {
+    pub dns_time: Duration,
+    pub connect_time: Duration,
+    /// TLS handshake time, for https URLs
+    pub tls_time: Option<Duration>,
+    /// Time from sending the request until the response headers arrived
+    pub time_to_first_byte: Duration,
+    pub total_time: Duration,
+    pub bytes: u64,
+    /// What the download was verified against, e.g. "Content-Length"
+    pub verified_against: Vec<&'static str>,
+    /// Average throughput in MB/s
+    pub average_throughput: f64,
+    /// Highest throughput in MB/s over a 250 ms window
+    pub peak_throughput: f64,
+    /// For https when `expected_ca` is set: `Ok` when the server's chain was
+    /// issued by the expected CA, otherwise why it was not
+    pub issued_by_expected_ca: Option<Result<(), String>>,
+}
+
+/// Aggregate metrics of downloads running concurrently.
+pub struct ParallelResults {
+    pub downloads: usize,
+    /// Time until the last download finished
+    pub total_time: Duration,
+    pub bytes: u64,
+    /// Combined throughput in MB/s
+    pub throughput: f64,
+}
 
 #[derive(Deserialize, Serialize)]
 struct Config {
@@ -11,7 +57,63 @@ struct Config {
 
 #[derive(Deserialize, Serialize)]
 struct DownloadConfig {
+    /// The unnamed target of configs that predate `targets`
+    #[serde(flatten)]
+    single: DownloadTarget,
+    /// Named targets; when present they replace the unnamed one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    targets: Vec<DownloadTarget>,
+    /// Number of concurrent downloads in the parallel phase, 0 to skip it
+    #[serde(default)]
+    parallel: usize,
+}
+
+#[derive(Deserialize, Serialize)]
+struct DownloadTarget {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+    #[serde(default)]
     url: String,
+    #[serde(default)]
+    output: String,
+    /// "remote" downloads `url`, "local" downloads from the embedded server
+    #[serde(default = "default_source")]
+    source: String,
+    /// Expected SHA-256 of the downloaded file, as hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+    /// Expected size of the downloaded file in bytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+}
+
+fn default_source() -> String {
+    "remote".to_string()
+}
+
+/// A target with its URL, output file and expectations worked out.
+#[derive(Clone)]
+struct Download {
+    url: String,
+    output: String,
+    size: Option<u64>,
+    sha256: Option<String>,
+    client: reqwest::Client,
+    /// Set for the embedded server, which is reached without the proxy and
+    /// usually has a self-signed certificate
+    local: bool,
+}
+
+/// Timings of the transfer itself, without the connection probe.
+struct Transfer {
+    time_to_first_byte: Duration,
+    total_time: Duration,
+    bytes: u64,
+    /// Size announced in the Content-Length header
+    content_length: Option<u64>,
+    peak_throughput: f64,
+    /// When the last byte was written
+    finished: Instant,
     output: String,
 }
 
@@ -35,8 +137,16 @@ impl DownloadOperations {
         } else {
             let default_config = Config {
                 download: DownloadConfig {
-                    url: "https://testing.taxi/wp-content/uploads/2023/06/compressed-txt-100M.zip".to_string(),
-                    output: "artifacts/downloaded_file.zip".to_string(),
+                    single: DownloadTarget {
+                        name: String::new(),
+                        url: "https://testing.taxi/wp-content/uploads/2023/06/compressed-txt-100M.zip".to_string(),
+                        output: "artifacts/downloaded_file.zip".to_string(),
+                        source: default_source(),
+                        sha256: None,
+                        size: None,
+                    },
+                    targets: Vec::new(),
+                    parallel: 0,
                 },
             };
             let toml_str = toml::to_string(&default_config)
@@ -47,32 +157,436 @@ impl DownloadOperations {
         }
     }
 
-    pub async fn perform_operation(&self) -> Result<(), String> {
+    pub async fn perform_operation(&self) -> Result<DownloadResults, String> {
         println!("Performing download operation...");
-        
-        let url = &self.config.download.url;
-        let output = &self.config.download.output;
 
-        let response = reqwest::get(url)
-            .await
-            .map_err(|e| format!("Failed to GET from {}: {}", url, e))?;
+        let download = &self.config.download;
+        let targets = if download.targets.is_empty() {
+            std::slice::from_ref(&download.single)
+        } else {
+            &download.targets[..]
+        };
+
         // Ensure artifacts directory exists
         std::fs::create_dir_all("artifacts")
             .map_err(|e| format!("Failed to create artifacts directory: {}", e))?;
 
-        let mut file = File::create(output)
+        // The server has to outlive the downloads, so it is kept here
+        let server = if targets.iter().any(|target| target.source == "local") {
+            let server_config = LocalServerConfig::load()?;
+            let server = LocalServer::start(&server_config).await?;
+            let client = reqwest::Client::builder()
+                .no_proxy()
+                .danger_accept_invalid_certs(true)
+                .build()
+                .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+            Some((server, server_config.size_mb * 1024 * 1024, client))
+        } else {
+            None
+        };
+
+        let http = HttpConfig::load()?;
+        let client = http.client()?;
+        let mut downloads = Vec::new();
+        for target in targets {
+            downloads.push(resolve(target, &client, server.as_ref())?);
+        }
+
+        let mut results = Vec::new();
+        for (target, download) in targets.iter().zip(&downloads) {
+            let label = if target.name.is_empty() {
+                "Download".to_string()
+            } else {
+                format!("Download {}", target.name)
+            };
+            println!("Downloading {}...", download.url);
+            results.push((label, download_target(download, &http).await));
+        }
+
+        let parallel = if download.parallel > 0 {
+            // Targets that already failed on their own would only fail again
+            let working: Vec<Download> = downloads
+                .iter()
+                .zip(&results)
+                .filter(|(_, (_, result))| result.is_ok())
+                .map(|(download, _)| download.clone())
+                .collect();
+            if working.is_empty() {
+                Some(Err("No download target succeeded".to_string()))
+            } else {
+                println!("Running {} downloads in parallel...", download.parallel);
+                Some(download_parallel(&working, download.parallel).await)
+            }
+        } else {
+            None
+        };
+
+        println!("Downloads finished.");
+        Ok(DownloadResults {
+            targets: results,
+            parallel,
+        })
+    }
+}
+
+/// Works out where a target is downloaded from and what it is verified against.
+fn resolve(
+    target: &DownloadTarget,
+    client: &reqwest::Client,
+    server: Option<&(LocalServer, u64, reqwest::Client)>,
+) -> Result<Download, String> {
+    let output = if target.output.is_empty() {
+        let name = if target.name.is_empty() { "downloaded_file" } else { &target.name };
+        format!("artifacts/{}.bin", name)
+    } else {
+        target.output.clone()
+    };
+
+    match (target.source.as_str(), server) {
+        ("remote", _) => {
+            if target.url.is_empty() {
+                return Err(format!("Download target '{}' has no url", target.name));
+            }
+            Ok(Download {
+                url: target.url.clone(),
+                output,
+                size: target.size,
+                sha256: target.sha256.clone(),
+                client: client.clone(),
+                local: false,
+            })
+        }
+        ("local", Some((server, default_size, local_client))) => {
+            let size = target.size.unwrap_or(*default_size);
+            // The generated payload is known, so it can always be verified
+            let sha256 = target
+                .sha256
+                .clone()
+                .unwrap_or_else(|| local_server::payload_sha256(size));
+            Ok(Download {
+                url: server.url(&format!("payload?bytes={}", size)),
+                output,
+                size: Some(size),
+                sha256: Some(sha256),
+                client: local_client.clone(),
+                local: true,
+            })
+        }
+        (other, _) => Err(format!(
+            "Unknown download source '{}', expected remote or local",
+            other
+        )),
+    }
+}
+
+async fn download_target(download: &Download, http: &HttpConfig) -> Result<TargetResults, String> {
+    let parsed = reqwest::Url::parse(&download.url)
+        .map_err(|e| format!("Invalid URL {}: {}", download.url, e))?;
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| format!("URL {} has no host", download.url))?;
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| format!("URL {} has no port", download.url))?;
+    let https = parsed.scheme() == "https";
+
+    // DNS, TCP connect and TLS are timed on a probe connection of their own,
+    // because reqwest does not expose them for the download connection
+    let (dns_time, connect_time, tls_time) = if download.local {
+        let connector = native_tls::TlsConnector::builder()
+            .danger_accept_invalid_certs(true)
+            .build()
+            .map_err(|e| format!("Failed to create TLS connector: {}", e))?;
+        let connection = HttpConfig::default().connect(host, port).await?;
+        probe_connection(connection, host, https, connector.into()).await?
+    } else {
+        let connection = http.connect(host, port).await?;
+        probe_connection(connection, host, https, http.tls_connector()?).await?
+    };
+
+    let issued_by_expected_ca = if https && !download.local && http.has_expected_ca() {
+        Some(http.check_expected_ca(host, port).await?)
+    } else {
+        None
+    };
+
+    let transfer = transfer(download.clone(), String::new()).await?;
+    let verified_against = verify(download, &transfer).await?;
+
+    let average_throughput = megabytes_per_second(transfer.bytes, transfer.total_time);
+    Ok(TargetResults {
+        dns_time,
+        connect_time,
+        tls_time,
+        time_to_first_byte: transfer.time_to_first_byte,
+        total_time: transfer.total_time,
+        bytes: transfer.bytes,
+        verified_against,
+        average_throughput,
+        // Downloads shorter than one window have no peak of their own
+        peak_throughput: transfer.peak_throughput.max(average_throughput),
+        issued_by_expected_ca,
+    })
+}
+
+/// Runs `count` downloads at once, cycling through the targets.
+async fn download_parallel(downloads: &[Download], count: usize) -> Result<ParallelResults, String> {
+    let start = Instant::now();
+    let mut tasks = JoinSet::new();
+    for (i, download) in downloads.iter().cycle().take(count).enumerate() {
+        let download = download.clone();
+        tasks.spawn(async move {
+            let transfer = transfer(download.clone(), format!(".{}", i + 1)).await?;
+            Ok::<_, String>((download, transfer))
+        });
+    }
+
+    let mut transfers = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        transfers.push(joined.map_err(|e| format!("Parallel download task failed: {}", e))??);
+    }
+
+    // Verified once all are done, so hashing does not slow down running downloads
+    let mut bytes = 0u64;
+    let mut finished = start;
+    for (download, transfer) in &transfers {
+        verify(download, transfer).await?;
+        bytes += transfer.bytes;
+        finished = finished.max(transfer.finished);
+    }
+
+    let elapsed = finished - start;
+    Ok(ParallelResults {
+        downloads: count,
+        total_time: elapsed,
+        bytes,
+        throughput: megabytes_per_second(bytes, elapsed),
+    })
+}
+
+/// Downloads to the target's output file with `suffix` appended.
+async fn transfer(download: Download, suffix: String) -> Result<Transfer, String> {
+    let url = &download.url;
+    let output = format!("{}{}", download.output, suffix);
+
+    let mut file = File::create(&output)
+        .await
+        .map_err(|e| format!("Failed to create file '{}': {}", output, e))?;
+
+    let start = Instant::now();
+    let mut response = download
+        .client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to GET from {}: {}", url, e))?
+        .error_for_status()
+        .map_err(|e| format!("Failed to GET from {}: {}", url, e))?;
+    let time_to_first_byte = start.elapsed();
+    let content_length = response.content_length();
+
+    // Stream to disk, tracking the throughput per window
+    let mut bytes = 0u64;
+    let mut peak_throughput = 0.0f64;
+    let mut window_start = Instant::now();
+    let mut window_bytes = 0u64;
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?
+    {
+        if process::cancelled() {
+            return Err(format!("Download from {} was cancelled", url));
+        }
+        file.write_all(&chunk)
             .await
-            .map_err(|e| format!("Failed to create file '{}': {}", output, e))?;
+            .map_err(|e| format!("Failed to write content to file: {}", e))?;
+        bytes += chunk.len() as u64;
+        window_bytes += chunk.len() as u64;
+
+        let window = window_start.elapsed();
+        if window >= THROUGHPUT_WINDOW {
+            peak_throughput = peak_throughput.max(megabytes_per_second(window_bytes, window));
+            window_start = Instant::now();
+            window_bytes = 0;
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| format!("Failed to write content to file: {}", e))?;
+    let finished = Instant::now();
 
-        let content = response.bytes()
+    Ok(Transfer {
+        time_to_first_byte,
+        total_time: finished - start,
+        bytes,
+        content_length,
+        peak_throughput,
+        finished,
+        output,
+    })
+}
+
+/// Checks a finished transfer against what was expected of it and returns what
+/// it was verified against. Runs after the clock stopped, so hashing is not timed.
+async fn verify(download: &Download, transfer: &Transfer) -> Result<Vec<&'static str>, String> {
+    let bytes = transfer.bytes;
+    let mut verified_against = Vec::new();
+    if let Some(expected_size) = transfer.content_length {
+        if bytes != expected_size {
+            return Err(format!(
+                "Downloaded {} bytes but the server announced {}",
+                bytes, expected_size
+            ));
+        }
+        verified_against.push("Content-Length");
+    }
+    if let Some(size) = download.size {
+        if bytes != size {
+            return Err(format!("Downloaded {} bytes but expected {}", bytes, size));
+        }
+        verified_against.push("size");
+    }
+    if let Some(expected_sha256) = &download.sha256 {
+        let sha256 = sha256_file(&transfer.output).await?;
+        if !sha256.eq_ignore_ascii_case(expected_sha256.trim()) {
+            return Err(format!(
+                "SHA-256 of {} is {} but expected {}",
+                transfer.output, sha256, expected_sha256
+            ));
+        }
+        verified_against.push("SHA-256");
+    }
+
+    Ok(verified_against)
+}
+
+/// Handshakes with `host` on the connection for https, returning the DNS,
+/// connect and TLS handshake times.
+async fn probe_connection(
+    connection: Connection,
+    host: &str,
+    https: bool,
+    connector: tokio_native_tls::TlsConnector,
+) -> Result<(Duration, Duration, Option<Duration>), String> {
+    let tls_time = if https {
+        let tls_start = Instant::now();
+        connector
+            .connect(host, connection.stream)
             .await
-            .map_err(|e| format!("Failed to get response bytes: {}", e))?;
+            .map_err(|e| format!("TLS handshake with {} failed: {}", host, e))?;
+        Some(tls_start.elapsed())
+    } else {
+        None
+    };
+
+    Ok((connection.dns_time, connection.connect_time, tls_time))
+}
 
-        file.write_all(&content)
+async fn sha256_file(path: &str) -> Result<String, String> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open '{}' for verification: {}", path, e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
             .await
-            .map_err(|e| format!("Failed to write content to file: {}", e))?;
+            .map_err(|e| format!("Failed to read '{}' for verification: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        if process::cancelled() {
+            return Err(format!("Verification of '{}' was cancelled", path));
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn megabytes_per_second(bytes: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        return 0.0;
+    }
+    bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `content` to a temporary file and describes a transfer that produced it.
+    fn transferred(name: &str, content: &[u8], content_length: Option<u64>) -> Transfer {
+        let output = std::env::temp_dir()
+            .join(format!("benchinator-verify-{}-{}.bin", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned();
+        std::fs::write(&output, content).unwrap();
+        Transfer {
+            time_to_first_byte: Duration::ZERO,
+            total_time: Duration::ZERO,
+            bytes: content.len() as u64,
+            content_length,
+            peak_throughput: 0.0,
+            finished: Instant::now(),
+            output,
+        }
+    }
+
+    fn download(size: Option<u64>, sha256: Option<String>) -> Download {
+        Download {
+            url: "http://127.0.0.1/payload".to_string(),
+            output: String::new(),
+            size,
+            sha256,
+            client: reqwest::Client::new(),
+            local: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn verifies_against_what_is_known() {
+        let hello_sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let transfer = transferred("match", b"hello", Some(5));
+        let verified = verify(&download(Some(5), Some(hello_sha256.to_uppercase())), &transfer).await;
+        assert_eq!(verified, Ok(vec!["Content-Length", "size", "SHA-256"]));
+
+        let transfer = transferred("match", b"hello", None);
+        assert_eq!(verify(&download(None, None), &transfer).await, Ok(vec![]));
+        std::fs::remove_file(&transfer.output).unwrap();
+    }
+
+    #[tokio::test]
+    async fn mismatches_are_errors() {
+        let transfer = transferred("mismatch", b"hello", Some(6));
+        let error = verify(&download(None, None), &transfer).await.unwrap_err();
+        assert_eq!(error, "Downloaded 5 bytes but the server announced 6");
+
+        let transfer = transferred("mismatch", b"hello", Some(5));
+        let error = verify(&download(Some(4), None), &transfer).await.unwrap_err();
+        assert_eq!(error, "Downloaded 5 bytes but expected 4");
+
+        let transfer = transferred("mismatch", b"hello", Some(5));
+        let error = verify(&download(Some(5), Some("00".repeat(32))), &transfer).await.unwrap_err();
+        assert!(error.starts_with("SHA-256 of "), "{}", error);
+        assert!(error.ends_with(&format!("but expected {}", "00".repeat(32))), "{}", error);
+        std::fs::remove_file(&transfer.output).unwrap();
+    }
+
+    #[tokio::test]
+    async fn local_payload_is_checked_against_its_checksum() {
+        // Larger than one block, so the payload repeats
+        let size = local_server::payload_block().len() as u64 + 1000;
+        let mut payload: Vec<u8> = local_server::payload_block().into_iter().cycle().take(size as usize).collect();
+        let expected = download(Some(size), Some(local_server::payload_sha256(size)));
+
+        let transfer = transferred("payload", &payload, Some(size));
+        assert_eq!(verify(&expected, &transfer).await, Ok(vec!["Content-Length", "size", "SHA-256"]));
 
-        println!("File downloaded successfully.");
-        Ok(())
+        payload[size as usize - 1] ^= 0xff;
+        let transfer = transferred("payload", &payload, Some(size));
+        assert!(verify(&expected, &transfer).await.unwrap_err().starts_with("SHA-256 of "));
+        std::fs::remove_file(&transfer.output).unwrap();
     }
 }
@@ -2,13 +2,19 @@ use std::io::{self, Read, Write};
 use std::fs::{self, File};
 use rand::Rng;
 use sha2::{Sha256, Digest};
+use std::time::Duration;
+use crate::process;
+use crate::units;
+
+/// Scratch file written and read by the file benchmarks.
+pub const TEMP_FILE: &str = "temp_benchmark_file.bin";
 
 pub struct FileOperationResults {
-    pub write_time: u128,
-    pub read_time: u128,
-    pub ram_load_time: u128,
-    pub disk_hash_time: u128,
-    pub ram_hash_time: u128,
+    pub write_time: Duration,
+    pub read_time: Duration,
+    pub ram_load_time: Duration,
+    pub disk_hash_time: Duration,
+    pub ram_hash_time: Duration,
 }
 
 pub struct FileOperations;
@@ -22,41 +28,42 @@ impl FileOperations {
         println!("Performing file operations...");
         
         let file_size = 500 * 1024 * 1024; // 500MB
-        let temp_file = "temp_benchmark_file.bin";
+        let temp_file = TEMP_FILE;
         
         // Measure write operation
         let write_start = std::time::Instant::now();
         self.write_random_file(temp_file, file_size)
             .map_err(|e| format!("Write operation failed: {}", e))?;
-        let write_time = write_start.elapsed().as_millis();
-        println!("Write operation completed in {} ms", write_time);
+        let write_time = write_start.elapsed();
+        println!("Write operation completed in {}", units::display_duration(write_time));
 
         // Measure read operation
         let read_start = std::time::Instant::now();
         self.read_file(temp_file)
             .map_err(|e| format!("Read operation failed: {}", e))?;
-        let read_time = read_start.elapsed().as_millis();
-        println!("Read operation completed in {} ms", read_time);
+        let read_time = read_start.elapsed();
+        println!("Read operation completed in {}", units::display_duration(read_time));
 
         // Measure RAM load operation
         let ram_load_start = std::time::Instant::now();
         let data = self.load_to_ram(temp_file)
             .map_err(|e| format!("RAM load operation failed: {}", e))?;
-        let ram_load_time = ram_load_start.elapsed().as_millis();
-        println!("RAM load operation completed in {} ms", ram_load_time);
+        let ram_load_time = ram_load_start.elapsed();
+        println!("RAM load operation completed in {}", units::display_duration(ram_load_time));
 
         // Measure disk hash calculation
         let disk_hash_start = std::time::Instant::now();
         let disk_hash = self.calculate_file_hash(temp_file)
             .map_err(|e| format!("Disk hash calculation failed: {}", e))?;
-        let disk_hash_time = disk_hash_start.elapsed().as_millis();
-        println!("File hash from disk: {} (completed in {} ms)", disk_hash, disk_hash_time);
+        let disk_hash_time = disk_hash_start.elapsed();
+        println!("File hash from disk: {} (completed in {})", disk_hash, units::display_duration(disk_hash_time));
 
         // Measure RAM hash calculation
         let ram_hash_start = std::time::Instant::now();
-        let ram_hash = self.calculate_ram_hash(&data);
-        let ram_hash_time = ram_hash_start.elapsed().as_millis();
-        println!("File hash from RAM: {} (completed in {} ms)", ram_hash, ram_hash_time);
+        let ram_hash = self.calculate_ram_hash(&data)
+            .map_err(|e| format!("RAM hash calculation failed: {}", e))?;
+        let ram_hash_time = ram_hash_start.elapsed();
+        println!("File hash from RAM: {} (completed in {})", ram_hash, units::display_duration(ram_hash_time));
 
         // Clean up
         fs::remove_file(temp_file)
@@ -77,6 +84,7 @@ impl FileOperations {
         let mut buffer = [0u8; 8192];
 
         for _ in 0..(size / buffer.len()) {
+            check_cancelled()?;
             rng.fill(&mut buffer[..]);
             file.write_all(&buffer)?;
         }
@@ -94,7 +102,9 @@ impl FileOperations {
         let mut file = File::open(filename)?;
         let mut buffer = [0u8; 8192];
         
-        while file.read(&mut buffer)? != 0 {}
+        while file.read(&mut buffer)? != 0 {
+            check_cancelled()?;
+        }
         
         Ok(())
     }
@@ -113,16 +123,28 @@ impl FileOperations {
             if bytes_read == 0 {
                 break;
             }
+            check_cancelled()?;
             hasher.update(&buffer[..bytes_read]);
         }
 
         Ok(format!("{:x}", hasher.finalize()))
     }
 
-    fn calculate_ram_hash(&self, data: &[u8]) -> String {
+    fn calculate_ram_hash(&self, data: &[u8]) -> io::Result<String> {
         let mut hasher = Sha256::new();
-        hasher.update(data);
-        format!("{:x}", hasher.finalize())
+        for chunk in data.chunks(8 * 1024 * 1024) {
+            check_cancelled()?;
+            hasher.update(chunk);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+/// Fails once the benchmark has been cancelled, so a timed out run stops early.
+fn check_cancelled() -> io::Result<()> {
+    if process::cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "benchmark was cancelled"));
     }
+    Ok(())
 }
 
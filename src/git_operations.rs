@@ -1,11 +1,11 @@
 use std::process::Command;
+use crate::process;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::Path;
 use rand::Rng;
 use rand::seq::SliceRandom;
 use serde::Deserialize;
-use toml;
 
 #[derive(Deserialize)]
 struct Config {
@@ -17,7 +17,7 @@ impl GitOperations {
         let files = fs::read_dir("artifacts")
             .map_err(|e| format!("Failed to read artifacts directory: {}", e))?
             .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "txt"))
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "txt"))
             .collect::<Vec<_>>();
 
         let mut rng = rand::thread_rng();
@@ -30,17 +30,19 @@ impl GitOperations {
             fs::remove_file(file.path())
                 .map_err(|e| format!("Failed to remove file '{}': {}", filename_str, e))?;
 
-            Command::new("git")
-                .current_dir("artifacts")
-                .args(&["rm", &filename_str])
-                .output()
-                .map_err(|e| format!("Failed to git rm file '{}': {}", filename_str, e))?;
-
-            Command::new("git")
-                .current_dir("artifacts")
-                .args(&["commit", "-m", &format!("Remove {}", filename_str)])
-                .output()
-                .map_err(|e| format!("Failed to commit removal of '{}': {}", filename_str, e))?;
+            process::output(
+                Command::new("git")
+                    .current_dir("artifacts")
+                    .args(["rm", &filename_str]),
+            )
+            .map_err(|e| format!("Failed to git rm file '{}': {}", filename_str, e))?;
+
+            process::output(
+                Command::new("git")
+                    .current_dir("artifacts")
+                    .args(["commit", "-m", &format!("Remove {}", filename_str)]),
+            )
+            .map_err(|e| format!("Failed to commit removal of '{}': {}", filename_str, e))?;
         }
 
         println!("{} files randomly removed from the repository.", count);
@@ -101,11 +103,12 @@ impl GitOperations {
     }
 
     fn init_repo(&self) -> Result<(), String> {
-        Command::new("git")
-            .current_dir("artifacts")
-            .args(&["init"])
-            .output()
-            .map_err(|e| format!("Failed to initialize git repository: {}", e))?;
+        process::output(
+            Command::new("git")
+                .current_dir("artifacts")
+                .args(["init"]),
+        )
+        .map_err(|e| format!("Failed to initialize git repository: {}", e))?;
 
         println!("Git repository initialized in artifacts directory.");
         Ok(())
@@ -117,11 +120,12 @@ impl GitOperations {
             .find(|b| b.starts_with("feature/"))
             .ok_or_else(|| "No feature branch found in config".to_string())?;
 
-        Command::new("git")
-            .current_dir("artifacts")
-            .args(&["checkout", "-b", branch_name])
-            .output()
-            .map_err(|e| format!("Failed to create test branch: {}", e))?;
+        process::output(
+            Command::new("git")
+                .current_dir("artifacts")
+                .args(["checkout", "-b", branch_name]),
+        )
+        .map_err(|e| format!("Failed to create test branch: {}", e))?;
 
         println!("Created and switched to feature branch '{}'", branch_name);
         Ok(())
@@ -140,17 +144,19 @@ impl GitOperations {
             file.write_all(content.as_bytes())
                 .map_err(|e| format!("Failed to write to file '{}': {}", filepath.display(), e))?;
 
-            Command::new("git")
-                .current_dir("artifacts")
-                .args(&["add", &filename])
-                .output()
-                .map_err(|e| format!("Failed to add file '{}' to git: {}", filename, e))?;
-
-            Command::new("git")
-                .current_dir("artifacts")
-                .args(&["commit", "-m", &format!("Add {}", filename)])
-                .output()
-                .map_err(|e| format!("Failed to commit file '{}': {}", filename, e))?;
+            process::output(
+                Command::new("git")
+                    .current_dir("artifacts")
+                    .args(["add", &filename]),
+            )
+            .map_err(|e| format!("Failed to add file '{}' to git: {}", filename, e))?;
+
+            process::output(
+                Command::new("git")
+                    .current_dir("artifacts")
+                    .args(["commit", "-m", &format!("Add {}", filename)]),
+            )
+            .map_err(|e| format!("Failed to commit file '{}': {}", filename, e))?;
         }
 
         println!("{} files created and committed in artifacts directory.", count);
@@ -0,0 +1,351 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Fingerprint of the machine a run was measured on. Fields that cannot be
+/// determined on this platform are left empty.
+#[derive(Serialize, Deserialize)]
+pub struct HostInfo {
+    pub hostname: String,
+    pub os: String,
+    /// Distribution name and version, e.g. "Ubuntu 22.04.4 LTS"
+    pub os_version: Option<String>,
+    pub kernel: Option<String>,
+    pub arch: String,
+    pub cpu_model: Option<String>,
+    /// Physical cores
+    pub cpu_cores: Option<usize>,
+    /// Logical CPUs available to the tool
+    pub cpus: usize,
+    pub memory_bytes: Option<u64>,
+    /// Block device holding the working directory, e.g. "nvme0n1p2"
+    pub disk_device: Option<String>,
+    pub disk_model: Option<String>,
+    /// Filesystem of the working directory, e.g. "ext4"
+    pub filesystem: Option<String>,
+    /// "ac", "battery" or "battery (<status>, <capacity>%)"
+    pub power_source: Option<String>,
+    pub cpu_governor: Option<String>,
+}
+
+impl HostInfo {
+    pub fn collect() -> Self {
+        let uname = Uname::get();
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let mount = working_directory_mount();
+        let disk_device = mount.as_ref().and_then(|m| block_device_name(&m.device));
+
+        HostInfo {
+            hostname: uname.nodename.unwrap_or_else(hostname),
+            os: std::env::consts::OS.to_string(),
+            os_version: os_release(),
+            kernel: uname.release,
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_model: cpuinfo_value(&cpuinfo, "model name"),
+            cpu_cores: physical_cores(&cpuinfo),
+            cpus: std::thread::available_parallelism().map_or(0, |n| n.get()),
+            memory_bytes: meminfo_total(),
+            disk_model: mount.as_ref().and_then(|m| disk_model(&m.device)),
+            disk_device,
+            filesystem: mount.map(|m| m.filesystem),
+            power_source: power_source(Path::new("/sys/class/power_supply")),
+            cpu_governor: read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+        }
+    }
+
+    pub fn print(&self) {
+        println!("Host: {} ({}, {})", self.hostname, self.os_version.as_deref().unwrap_or(&self.os), self.arch);
+        if let Some(kernel) = &self.kernel {
+            println!("Kernel: {}", kernel);
+        }
+        let cores = self.cpu_cores.map_or(String::new(), |cores| format!("{} cores, ", cores));
+        println!(
+            "CPU: {} ({}{} threads)",
+            self.cpu_model.as_deref().unwrap_or("unknown"),
+            cores,
+            self.cpus
+        );
+        if let Some(memory) = self.memory_bytes {
+            println!("Memory: {} MB", memory / (1024 * 1024));
+        }
+        if let Some(device) = &self.disk_device {
+            println!(
+                "Disk: {} ({}, {})",
+                device,
+                self.disk_model.as_deref().unwrap_or("unknown model"),
+                self.filesystem.as_deref().unwrap_or("unknown filesystem")
+            );
+        }
+        if let Some(power_source) = &self.power_source {
+            println!("Power source: {}", power_source);
+        }
+        if let Some(governor) = &self.cpu_governor {
+            println!("CPU governor: {}", governor);
+        }
+    }
+}
+
+struct Uname {
+    nodename: Option<String>,
+    release: Option<String>,
+}
+
+impl Uname {
+    #[cfg(unix)]
+    fn get() -> Self {
+        let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+        if unsafe { libc::uname(&mut name) } != 0 {
+            return Uname { nodename: None, release: None };
+        }
+        let field = |chars: &[libc::c_char]| {
+            let bytes: Vec<u8> = chars.iter().take_while(|&&c| c != 0).map(|&c| c as u8).collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned()).filter(|s| !s.is_empty())
+        };
+        Uname {
+            nodename: field(&name.nodename),
+            release: field(&name.release),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn get() -> Self {
+        Uname { nodename: None, release: None }
+    }
+}
+
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_default()
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+fn os_release() -> Option<String> {
+    let content = fs::read_to_string("/etc/os-release").ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|name| name.trim_matches('"').to_string())
+}
+
+fn cpuinfo_value(cpuinfo: &str, key: &str) -> Option<String> {
+    cpuinfo.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim().to_string())
+    })
+}
+
+/// Counts distinct (physical id, core id) pairs, which virtual machines often omit.
+fn physical_cores(cpuinfo: &str) -> Option<usize> {
+    let mut cores = HashSet::new();
+    for processor in cpuinfo.split("\n\n") {
+        let physical_id = cpuinfo_value(processor, "physical id");
+        if let Some(core_id) = cpuinfo_value(processor, "core id") {
+            cores.insert((physical_id, core_id));
+        }
+    }
+    Some(cores.len()).filter(|&count| count > 0)
+}
+
+fn meminfo_total() -> Option<u64> {
+    let content = fs::read_to_string("/proc/meminfo").ok()?;
+    let kilobytes = content
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+struct Mount {
+    /// "major:minor" of the device
+    device: String,
+    filesystem: String,
+}
+
+/// Finds the mount holding the working directory, where the benchmarks write their files.
+fn working_directory_mount() -> Option<Mount> {
+    let cwd = std::env::current_dir().ok()?.canonicalize().ok()?;
+    let content = fs::read_to_string("/proc/self/mountinfo").ok()?;
+
+    let mut best: Option<(usize, Mount)> = None;
+    for line in content.lines() {
+        // ID, parent ID, major:minor, root, mount point, options..., "-", type, source, ...
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(separator) = fields.iter().position(|&field| field == "-") else {
+            continue;
+        };
+        let (Some(device), Some(mount_point), Some(filesystem)) =
+            (fields.get(2), fields.get(4), fields.get(separator + 1))
+        else {
+            continue;
+        };
+        let mount_point = unescape_mount_point(mount_point);
+        let len = mount_point.as_os_str().len();
+        if cwd.starts_with(&mount_point) && best.as_ref().is_none_or(|(best_len, _)| len >= *best_len) {
+            let mount = Mount {
+                device: device.to_string(),
+                filesystem: filesystem.to_string(),
+            };
+            best = Some((len, mount));
+        }
+    }
+    best.map(|(_, mount)| mount)
+}
+
+/// Mount points escape spaces and other special characters as octal.
+fn unescape_mount_point(mount_point: &str) -> PathBuf {
+    let mut bytes = Vec::with_capacity(mount_point.len());
+    let mut rest = mount_point.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'\\' && tail.len() >= 3 {
+            if let Ok(value) = u8::from_str_radix(&String::from_utf8_lossy(&tail[..3]), 8) {
+                bytes.push(value);
+                rest = &tail[3..];
+                continue;
+            }
+        }
+        bytes.push(byte);
+        rest = tail;
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn block_device_name(device: &str) -> Option<String> {
+    let path = fs::canonicalize(format!("/sys/dev/block/{}", device)).ok()?;
+    path.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Model of the disk behind a device, looking through partitions and device-mapper
+/// or RAID devices to the first disk that reports one.
+fn disk_model(device: &str) -> Option<String> {
+    let mut path = fs::canonicalize(format!("/sys/dev/block/{}", device)).ok()?;
+    for _ in 0..8 {
+        if path.join("partition").exists() {
+            path = path.parent()?.to_path_buf();
+        }
+        if let Some(model) = read_trimmed(path.join("device/model")) {
+            return Some(model);
+        }
+        let slave = fs::read_dir(path.join("slaves")).ok()?.next()?.ok()?;
+        path = fs::canonicalize(slave.path()).ok()?;
+    }
+    None
+}
+
+fn power_source(power_supply: &Path) -> Option<String> {
+    let mut on_mains = false;
+    let mut battery = None;
+    for entry in fs::read_dir(power_supply).ok()?.flatten() {
+        let path = entry.path();
+        match read_trimmed(path.join("type")).as_deref() {
+            Some("Mains") => on_mains |= read_trimmed(path.join("online")).as_deref() == Some("1"),
+            Some("Battery") => {
+                battery = Some((read_trimmed(path.join("status")), read_trimmed(path.join("capacity"))))
+            }
+            _ => {}
+        }
+    }
+    match battery {
+        _ if on_mains => Some("ac".to_string()),
+        Some((Some(status), Some(capacity))) => Some(format!("battery ({}, {}%)", status, capacity)),
+        Some(_) => Some("battery".to_string()),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CPUINFO: &str = "processor\t: 0\nmodel name\t: Example CPU @ 3.00GHz\nphysical id\t: 0\ncore id\t\t: 0\n\n\
+processor\t: 1\nmodel name\t: Example CPU @ 3.00GHz\nphysical id\t: 0\ncore id\t\t: 1\n\n\
+processor\t: 2\nmodel name\t: Example CPU @ 3.00GHz\nphysical id\t: 0\ncore id\t\t: 0\n\n\
+processor\t: 3\nmodel name\t: Example CPU @ 3.00GHz\nphysical id\t: 1\ncore id\t\t: 0\n";
+
+    #[test]
+    fn reads_cpuinfo_values() {
+        assert_eq!(cpuinfo_value(CPUINFO, "model name").as_deref(), Some("Example CPU @ 3.00GHz"));
+        assert_eq!(cpuinfo_value(CPUINFO, "core id").as_deref(), Some("0"));
+        assert_eq!(cpuinfo_value(CPUINFO, "model"), None);
+        assert_eq!(cpuinfo_value("flags\t: fpu vme : de\n", "flags").as_deref(), Some("fpu vme : de"));
+    }
+
+    #[test]
+    fn counts_distinct_physical_cores() {
+        // Processors 0 and 2 are hyperthreads of one core; processor 3 sits in another socket.
+        assert_eq!(physical_cores(CPUINFO), Some(3));
+        // Virtual machines often leave out the topology entirely.
+        assert_eq!(physical_cores("processor\t: 0\n\nprocessor\t: 1\n"), None);
+        // Without physical ids the core ids are still distinct.
+        assert_eq!(physical_cores("core id\t: 0\n\ncore id\t: 1\n"), Some(2));
+    }
+
+    #[test]
+    fn unescapes_octal_sequences_in_mount_points() {
+        assert_eq!(unescape_mount_point("/"), PathBuf::from("/"));
+        assert_eq!(unescape_mount_point("/mnt/my\\040disk"), PathBuf::from("/mnt/my disk"));
+        assert_eq!(unescape_mount_point("/a\\011b\\012c\\134d"), PathBuf::from("/a\tb\nc\\d"));
+        // Backslashes that don't start an octal escape are kept.
+        assert_eq!(unescape_mount_point("/odd\\x"), PathBuf::from("/odd\\x"));
+        assert_eq!(unescape_mount_point("/end\\04"), PathBuf::from("/end\\04"));
+    }
+
+    /// Power supply directories by name, each with its attribute files and their contents.
+    type Supplies<'a> = &'a [(&'a str, &'a [(&'a str, &'a str)])];
+
+    fn power_supply(supplies: Supplies) -> PathBuf {
+        static COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("benchinator-power-{}-{}", std::process::id(), count));
+        let _ = fs::remove_dir_all(&dir);
+        for (name, files) in supplies {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            for (file, content) in *files {
+                fs::write(dir.join(name).join(file), format!("{}\n", content)).unwrap();
+            }
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn describes_the_power_source() {
+        let cases: &[(Supplies, Option<&str>)] = &[
+            (&[], None),
+            (&[("AC", &[("type", "Mains"), ("online", "1")])], Some("ac")),
+            (
+                &[
+                    ("AC", &[("type", "Mains"), ("online", "1")]),
+                    ("BAT0", &[("type", "Battery"), ("status", "Charging"), ("capacity", "80")]),
+                ],
+                Some("ac"),
+            ),
+            (
+                &[
+                    ("AC", &[("type", "Mains"), ("online", "0")]),
+                    ("BAT0", &[("type", "Battery"), ("status", "Discharging"), ("capacity", "57")]),
+                ],
+                Some("battery (Discharging, 57%)"),
+            ),
+            (&[("BAT1", &[("type", "Battery")])], Some("battery")),
+            (&[("hid-mouse", &[("type", "USB")])], None),
+        ];
+        for (supplies, expected) in cases {
+            let dir = power_supply(supplies);
+            assert_eq!(power_source(&dir).as_deref(), *expected, "{:?}", supplies);
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        assert_eq!(power_source(Path::new("/nonexistent/power_supply")), None);
+    }
+}
@@ -0,0 +1,284 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// Payloads repeat a block of seeded random bytes, so they do not compress
+/// and are identical on every run.
+const BLOCK_SIZE: usize = 64 * 1024;
+const PAYLOAD_SEED: u64 = 0x6265_6e63_6869_6e61;
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    local_server: LocalServerConfig,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LocalServerConfig {
+    #[serde(default = "default_size_mb")]
+    pub size_mb: u64,
+    /// Upper bound for the served data rate in MB/s, 0 for unthrottled
+    #[serde(default)]
+    pub throttle_mbps: f64,
+    /// PKCS#12 file with the certificate and key; serves HTTPS when set
+    #[serde(default)]
+    pub tls_identity: Option<String>,
+    #[serde(default)]
+    pub tls_identity_password: String,
+}
+
+impl Default for LocalServerConfig {
+    fn default() -> Self {
+        LocalServerConfig {
+            size_mb: default_size_mb(),
+            throttle_mbps: 0.0,
+            tls_identity: None,
+            tls_identity_password: String::new(),
+        }
+    }
+}
+
+fn default_size_mb() -> u64 {
+    100
+}
+
+impl LocalServerConfig {
+    pub fn load() -> Result<Self, String> {
+        let config_str = std::fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(config.local_server)
+    }
+}
+
+/// HTTP(S) server on localhost that serves generated payloads. It stops when dropped.
+pub struct LocalServer {
+    address: SocketAddr,
+    tls: bool,
+    task: JoinHandle<()>,
+}
+
+impl LocalServer {
+    pub async fn start(config: &LocalServerConfig) -> Result<Self, String> {
+        let acceptor = match &config.tls_identity {
+            Some(path) => {
+                let der = std::fs::read(path)
+                    .map_err(|e| format!("Failed to read TLS identity '{}': {}", path, e))?;
+                let identity = native_tls::Identity::from_pkcs12(&der, &config.tls_identity_password)
+                    .map_err(|e| format!("Failed to load TLS identity '{}': {}", path, e))?;
+                let acceptor = native_tls::TlsAcceptor::new(identity)
+                    .map_err(|e| format!("Failed to create TLS acceptor: {}", e))?;
+                Some(Arc::new(tokio_native_tls::TlsAcceptor::from(acceptor)))
+            }
+            None => None,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("Failed to start local server: {}", e))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start local server: {}", e))?;
+
+        let tls = acceptor.is_some();
+        let default_size = config.size_mb * 1024 * 1024;
+        let throttle = config.throttle_mbps * 1024.0 * 1024.0;
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let _ = match acceptor {
+                        Some(acceptor) => match acceptor.accept(stream).await {
+                            Ok(stream) => serve(stream, default_size, throttle).await,
+                            Err(_) => Ok(()),
+                        },
+                        None => serve(stream, default_size, throttle).await,
+                    };
+                });
+            }
+        });
+
+        println!("Local server listening on {}", address);
+        Ok(LocalServer { address, tls, task })
+    }
+
+    /// URL of `path` on this server, e.g. `http://127.0.0.1:41234/payload`. It
+    /// uses the bound address, because `localhost` may resolve to ::1 first.
+    pub fn url(&self, path: &str) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}/{}", scheme, self.address, path.trim_start_matches('/'))
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The block the server repeats to build payloads.
+pub fn payload_block() -> Vec<u8> {
+    let mut block = vec![0u8; BLOCK_SIZE];
+    StdRng::seed_from_u64(PAYLOAD_SEED).fill(&mut block[..]);
+    block
+}
+
+/// SHA-256, as hex, of a payload of `size` bytes.
+pub fn payload_sha256(size: u64) -> String {
+    let block = payload_block();
+    let mut hasher = Sha256::new();
+    let mut remaining = size;
+    while remaining > 0 {
+        let len = remaining.min(block.len() as u64) as usize;
+        hasher.update(&block[..len]);
+        remaining -= len as u64;
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Handles one request: `GET /payload` sends the configured payload size,
+/// `GET /payload?bytes=N` sends N bytes, and `POST` or `PUT` to `/upload`
+/// discards the body and answers with the number of bytes received.
+async fn serve<S>(stream: S, default_size: u64, throttle: f64) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut stream = BufReader::new(stream);
+    let mut request_line = String::new();
+    stream.read_line(&mut request_line).await?;
+
+    let mut content_length = 0u64;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        if stream.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    if matches!(method, "POST" | "PUT") && path == "/upload" {
+        let received = tokio::io::copy(&mut (&mut stream).take(content_length), &mut tokio::io::sink()).await?;
+        let body = received.to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        return stream.shutdown().await;
+    }
+
+    if method != "GET" || path != "/payload" {
+        let body = "not found\n";
+        let response = format!(
+            "HTTP/1.1 404 Not Found\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        return stream.shutdown().await;
+    }
+
+    let size = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("bytes="))
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(default_size);
+    let headers = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        size
+    );
+    stream.write_all(headers.as_bytes()).await?;
+
+    let block = payload_block();
+    let start = Instant::now();
+    let mut sent = 0u64;
+    while sent < size {
+        let len = (size - sent).min(block.len() as u64) as usize;
+        stream.write_all(&block[..len]).await?;
+        sent += len as u64;
+
+        if throttle > 0.0 {
+            let due = Duration::from_secs_f64(sent as f64 / throttle);
+            if let Some(ahead) = due.checked_sub(start.elapsed()) {
+                tokio::time::sleep(ahead).await;
+            }
+        }
+    }
+
+    stream.flush().await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(client: &reqwest::Client, url: &str) -> (u16, Vec<u8>) {
+        let response = client.get(url).send().await.unwrap();
+        let status = response.status().as_u16();
+        (status, response.bytes().await.unwrap().to_vec())
+    }
+
+    fn sha256(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    #[tokio::test]
+    async fn serves_the_payload_over_loopback() {
+        let config = LocalServerConfig {
+            size_mb: 1,
+            ..LocalServerConfig::default()
+        };
+        let server = LocalServer::start(&config).await.unwrap();
+        assert!(server.url("/payload").starts_with("http://127.0.0.1:"));
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+
+        // Shorter than a block, several blocks and a partial last block
+        for size in [0, 1000, 3 * BLOCK_SIZE as u64 + 123] {
+            let (status, body) = get(&client, &server.url(&format!("payload?bytes={}", size))).await;
+            assert_eq!(status, 200);
+            assert_eq!(body.len() as u64, size);
+            assert_eq!(sha256(&body), payload_sha256(size));
+        }
+
+        let (status, body) = get(&client, &server.url("payload")).await;
+        assert_eq!(status, 200);
+        assert_eq!(body.len(), 1024 * 1024);
+        assert_eq!(sha256(&body), payload_sha256(1024 * 1024));
+
+        let (status, _) = get(&client, &server.url("elsewhere")).await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn counts_uploaded_bytes() {
+        let server = LocalServer::start(&LocalServerConfig::default()).await.unwrap();
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let response = client
+            .post(server.url("upload"))
+            .body(vec![7u8; 200_000])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "200000");
+    }
+}
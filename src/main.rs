@@ -1,24 +1,53 @@
 mod build_run_operations;
 mod csv_writer;
+mod dns_operations;
+mod docker_api;
 mod docker_operations;
 mod download_operations;
 mod file_operations;
 mod git_operations;
+mod host_info;
+mod http_client;
+mod local_server;
+mod network_operations;
+mod noise_monitor;
+mod prerequisites;
+mod process;
+mod resource_usage;
+mod results;
+mod scoring;
+mod security_software;
+mod thermal;
+mod units;
+mod upload_operations;
 mod vscode;
 
 use build_run_operations::BuildRunOperations;
 use charts::{Chart, Color, ScaleBand, ScaleLinear, VerticalBarView};
 use chrono::Local;
-use csv_writer::CsvWriter;
+use csv_writer::{AverageRow, CsvWriter, ResultRow};
+use dns_operations::DnsOperations;
 use docker_operations::DockerOperations;
 use download_operations::DownloadOperations;
 use file_operations::FileOperations;
 use git_operations::GitOperations;
+use network_operations::NetworkOperations;
+use noise_monitor::NoiseMonitor;
+use prerequisites::Prerequisites;
+use process::Timeouts;
+use resource_usage::{ResourceUsage, UsageMeter};
+use results::{RunResults, Status};
+use scoring::Scoring;
+use security_software::SecurityDetector;
+use thermal::Thermal;
+use upload_operations::UploadOperations;
 use vscode::VsCodeOperations;
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
 use toml::Value;
 
 fn ensure_config_and_directories() -> Result<(), Box<dyn std::error::Error>> {
@@ -30,6 +59,42 @@ fn ensure_config_and_directories() -> Result<(), Box<dyn std::error::Error>> {
             [download]\n\
             url = \"https://testing.taxi/wp-content/uploads/2023/06/compressed-txt-100M.zip\"\n\
             output = \"downloaded_file.zip\"\n\
+            source = \"remote\"\n\
+            # sha256 = \"\"\n\
+            # size = 0\n\
+            parallel = 0\n\
+            \n\
+            [http]\n\
+            # proxy = \"http://proxy.example.com:8080\"\n\
+            # ca_bundle = \"certs/corporate-ca.pem\"\n\
+            # client_identity = \"certs/client.p12\"\n\
+            # client_identity_password = \"\"\n\
+            # min_tls_version = \"1.2\"\n\
+            # expected_ca = \"certs/corporate-ca.pem\"\n\
+            \n\
+            [upload]\n\
+            source = \"local\"\n\
+            # url = \"https://upload.example.com/sink\"\n\
+            method = \"POST\"\n\
+            size_mb = 50\n\
+            \n\
+            [network]\n\
+            iterations = 100\n\
+            message_bytes = 64\n\
+            bulk_mb = 100\n\
+            # remote = \"192.168.1.10:7878\"\n\
+            \n\
+            [dns]\n\
+            hostnames = [\"localhost\", \"github.com\", \"pypi.org\"]\n\
+            iterations = 20\n\
+            stub_resolver = true\n\
+            # server = \"10.0.0.53:53\"\n\
+            \n\
+            [local_server]\n\
+            size_mb = 100\n\
+            throttle_mbps = 0\n\
+            # tls_identity = \"certs/localhost.p12\"\n\
+            # tls_identity_password = \"\"\n\
             \n\
             [git]\n\
             files_count = 50\n\
@@ -40,9 +105,63 @@ fn ensure_config_and_directories() -> Result<(), Box<dyn std::error::Error>> {
             [docker]\n\
             image = \"af2.corpo.t-mobile.pl/cindy-base-images/python:3.9.7-slim-buster\"\n\
             test_command = [\"python\", \"--version\"]\n\
+            runtime = \"auto\"\n\
+            backend = \"cli\"\n\
+            prune = false\n\
+            pull_source = \"remote\"\n\
+            \n\
+            [vscode]\n\
+            headless = true\n\
+            ready_timeout_secs = 60\n\
+            # vsix = \"extensions/sample.vsix\"\n\
+            \n\
+            [security]\n\
+            active_run_types = [\"security_on\"]\n\
+            inactive_run_types = [\"security_off\"]\n\
+            # Replaces the built-in list of known agents\n\
+            # [[security.agents]]\n\
+            # name = \"Example EDR\"\n\
+            # processes = [\"example-agent\"]\n\
+            # modules = [\"example_*\"]\n\
+            # services = [\"example-agent\"]\n\
+            # packages = [\"example-agent\"]\n\
+            \n\
+            [noise]\n\
+            enabled = true\n\
+            interval_ms = 500\n\
+            top_processes = 3\n\
+            max_background_cpu = 25.0\n\
+            max_background_disk = 20.0\n\
+            max_memory_pressure = 10.0\n\
+            \n\
+            [thermal]\n\
+            # zones = [\"x86_pkg_temp\"]\n\
+            cool_down = false\n\
+            cool_down_celsius = 60.0\n\
+            cool_down_timeout_secs = 300\n\
+            \n\
+            [scoring]\n\
+            baseline = \"security_off\"\n\
+            \n\
+            [scoring.weights]\n\
+            file = 1.0\n\
+            git = 1.0\n\
+            docker = 1.0\n\
+            download = 1.0\n\
+            upload = 1.0\n\
+            network = 1.0\n\
+            dns = 1.0\n\
+            build_run = 1.0\n\
+            vscode = 1.0\n\
+            resources = 0.0\n\
+            \n\
+            [timeouts]\n\
+            default_secs = 600\n\
+            build_run = 120\n\
             \n\
             [runs]\n\
-            names = [\"security_off\", \"security_on\"]\n",
+            names = [\"security_off\", \"security_on\"]\n\
+            iterations = 1\n",
         )?;
         println!("Created config.toml with default settings.");
     }
@@ -58,130 +177,315 @@ fn ensure_config_and_directories() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn benchmark<F, Fut, T>(f: F) -> Result<u128, String>
+/// How a benchmark ended.
+enum Outcome<T> {
+    Completed(T),
+    Failed(String),
+    TimedOut { elapsed: Duration, output: String },
+}
+
+/// How long a timed-out benchmark gets to report the output of its killed processes.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Runs a blocking benchmark on its own thread. If it does not finish within
+/// `timeout`, the process trees it started are killed and the error it returns
+/// with their captured output is kept. A thread that is still running after
+/// that cannot start processes anymore and stops at its next cancellation check.
+async fn run_blocking<F, T>(timeout: Duration, f: F) -> Outcome<T>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    let generation = process::reset();
+    let start = Instant::now();
+    let (sender, mut receiver) = oneshot::channel();
+    std::thread::spawn(move || {
+        process::enter(generation);
+        let _ = sender.send(f());
+    });
+
+    match tokio::time::timeout(timeout, &mut receiver).await {
+        Ok(Ok(Ok(value))) => Outcome::Completed(value),
+        Ok(Ok(Err(e))) => Outcome::Failed(e),
+        Ok(Err(_)) => Outcome::Failed("Benchmark panicked".to_string()),
+        Err(_) => {
+            let elapsed = start.elapsed();
+            process::kill_all();
+            let output = match tokio::time::timeout(KILL_GRACE_PERIOD, receiver).await {
+                Ok(Ok(Err(e))) => e,
+                _ => String::new(),
+            };
+            Outcome::TimedOut { elapsed, output }
+        }
+    }
+}
+
+async fn benchmark<F, T>(timeout: Duration, f: F) -> Outcome<Duration>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    run_blocking(timeout, move || {
+        let start = Instant::now();
+        f()?;
+        Ok(start.elapsed())
+    })
+    .await
+}
+
+/// Runs an async benchmark, dropping it when it does not finish within `timeout`.
+async fn run_async<Fut, T>(timeout: Duration, f: Fut) -> Outcome<T>
 where
-    F: FnOnce() -> Fut,
     Fut: std::future::Future<Output = Result<T, String>>,
 {
+    process::reset();
     let start = Instant::now();
-    f().await?;
-    Ok(start.elapsed().as_millis())
+    match tokio::time::timeout(timeout, f).await {
+        Ok(Ok(value)) => Outcome::Completed(value),
+        Ok(Err(e)) => Outcome::Failed(e),
+        Err(_) => {
+            process::kill_all();
+            Outcome::TimedOut {
+                elapsed: start.elapsed(),
+                output: String::new(),
+            }
+        }
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Starting benchmarks...");
+fn write_result(results: &mut RunResults, operation: &str, time: Duration) -> std::io::Result<()> {
+    write_result_details(results, operation, time, "")
+}
 
-    // Ensure config, artifacts, and runs directories exist
-    ensure_config_and_directories()?;
+fn write_result_details(
+    results: &mut RunResults,
+    operation: &str,
+    time: Duration,
+    details: &str,
+) -> std::io::Result<()> {
+    write_metric(results, operation, units::millis(time), "ms", details)
+}
 
-    let file_ops = FileOperations::new();
-    let git_ops = GitOperations::new().map_err(|e| e.to_string())?;
-    let docker_ops = DockerOperations::new().map_err(|e| e.to_string())?;
-    let download_ops = DownloadOperations::new().map_err(|e| e.to_string())?;
-    let build_run_ops = BuildRunOperations::new();
-    let vscode_ops = VsCodeOperations::new();
-    let mut csv_writer = CsvWriter::new("artifacts/benchmark_results.csv")?;
-
-    // Write header to CSV
-    println!("Writing CSV header...");
-    csv_writer.write_row(&["Operation", "Time (ms)"])?;
-    csv_writer.flush()?;
+fn write_metric(
+    results: &mut RunResults,
+    operation: &str,
+    value: f64,
+    unit: &str,
+    details: &str,
+) -> std::io::Result<()> {
+    let formatted = units::display(value, unit);
+    if details.is_empty() {
+        println!("{}: {}", operation, formatted);
+    } else {
+        println!("{}: {} ({})", operation, formatted, details);
+    }
+    results.record(operation, Some(value), unit, Status::Ok, details)
+}
 
-    // Implement benchmark tests and write results immediately
-    let file_op_results = file_ops.perform_operation()?;
-    println!("Writing File Operation results...");
-    csv_writer.write_row(&[
-        "File Write Operation",
-        &file_op_results.write_time.to_string(),
-    ])?;
-    csv_writer.write_row(&[
-        "File Read Operation",
-        &file_op_results.read_time.to_string(),
-    ])?;
-    csv_writer.write_row(&[
-        "RAM Load Operation",
-        &file_op_results.ram_load_time.to_string(),
-    ])?;
-    csv_writer.write_row(&[
-        "Disk Hash Operation",
-        &file_op_results.disk_hash_time.to_string(),
-    ])?;
-    csv_writer.write_row(&[
-        "RAM Hash Operation",
-        &file_op_results.ram_hash_time.to_string(),
-    ])?;
-    csv_writer.flush()?;
+fn write_skipped(results: &mut RunResults, operation: &str, reason: &str) -> std::io::Result<()> {
+    println!("{}: skipped ({})", operation, reason);
+    results.record(operation, None, "", Status::Skipped, reason)
+}
 
-    let total_file_time = file_op_results.write_time
-        + file_op_results.read_time
-        + file_op_results.ram_load_time
-        + file_op_results.disk_hash_time
-        + file_op_results.ram_hash_time;
+fn write_failed(results: &mut RunResults, operation: &str, error: &str) -> std::io::Result<()> {
+    // Tool output can span several lines; keep the row on one line
+    let error = error.lines().find(|l| !l.trim().is_empty()).unwrap_or("unknown error").trim();
+    println!("{}: failed ({})", operation, error);
+    results.record(operation, None, "", Status::Failed, error)
+}
 
-    let git_op_time = benchmark(|| async { git_ops.perform_operation() }).await?;
-    println!("Writing Git Operation result...");
-    csv_writer.write_row(&["Git Operation", &git_op_time.to_string()])?;
-    csv_writer.flush()?;
+fn write_timed_out(
+    results: &mut RunResults,
+    operation: &str,
+    elapsed: Duration,
+    output: &str,
+) -> std::io::Result<()> {
+    let elapsed = units::display_duration(elapsed);
+    println!("{}: timed out after {}", operation, elapsed);
+    if !output.trim().is_empty() {
+        println!("Captured output:\n{}", output.trim_end());
+    }
 
-    docker_ops.perform_operation()?; // Docker system prune
-    let docker_op_time = benchmark(|| async { docker_ops.perform_operation() }).await?;
-    println!("Writing Docker Operation result...");
-    csv_writer.write_row(&["Docker Operation", &docker_op_time.to_string()])?;
-    csv_writer.flush()?;
+    // Keep the last lines of output, which usually show where it hung
+    let lines: Vec<&str> = output.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let tail = lines[lines.len().saturating_sub(3)..].join(" | ");
+    let details = format!("after {}: {}", elapsed, tail);
+    results.record(operation, None, "", Status::TimedOut, details.trim_end_matches([':', ' ']))
+}
 
-    let download_op_time = benchmark(|| async { download_ops.perform_operation().await }).await?;
-    println!("Writing Download Operation result...");
-    csv_writer.write_row(&["Download Operation", &download_op_time.to_string()])?;
-    csv_writer.flush()?;
+/// Waits for the CPU to cool down before the next benchmark, if configured.
+async fn cool_down(results: &mut RunResults, thermal: &Thermal) {
+    if let Err(e) = thermal.cool_down().await {
+        let warning = format!("cool-down before the {} benchmark: {}", results.category(), e);
+        results.warn(warning);
+    }
+}
 
-    let build_run_op_time = benchmark(|| async { build_run_ops.perform_operation() }).await?;
-    println!("Writing Build and Run Operation result...");
-    csv_writer.write_row(&["Build and Run Operation", &build_run_op_time.to_string()])?;
-    csv_writer.flush()?;
+/// Records what a benchmark used in the "resources" category, which is not
+/// scored unless it is given a weight.
+fn write_resources(
+    results: &mut RunResults,
+    benchmark: &str,
+    usage: Option<ResourceUsage>,
+) -> std::io::Result<()> {
+    let Some(usage) = usage else {
+        return Ok(());
+    };
+    println!(
+        "{} resource usage: {} user and {} system CPU, {} voluntary context switches, {} read, {} written",
+        benchmark,
+        units::display(usage.user_cpu_ms, "ms"),
+        units::display(usage.system_cpu_ms, "ms"),
+        usage.voluntary_switches,
+        units::display(usage.read_bytes as f64, "bytes"),
+        units::display(usage.write_bytes as f64, "bytes")
+    );
 
-    let vscode_op_time = benchmark(|| async { vscode_ops.open_branches() }).await?;
-    println!("Writing VS Code Operation result...");
-    csv_writer.write_row(&["VS Code Operation", &vscode_op_time.to_string()])?;
-    csv_writer.flush()?;
+    let mut metrics = vec![
+        ("User CPU Time", usage.user_cpu_ms, "ms"),
+        ("System CPU Time", usage.system_cpu_ms, "ms"),
+        ("Child CPU Time", usage.children_cpu_ms, "ms"),
+        ("Voluntary Context Switches", usage.voluntary_switches as f64, "switches"),
+        ("Involuntary Context Switches", usage.involuntary_switches as f64, "switches"),
+        ("Minor Page Faults", usage.minor_faults as f64, "faults"),
+        ("Major Page Faults", usage.major_faults as f64, "faults"),
+        ("Disk Read", usage.read_bytes as f64, "bytes"),
+        ("Disk Write", usage.write_bytes as f64, "bytes"),
+    ];
+    if let Some(peak_rss_mb) = usage.peak_rss_mb {
+        metrics.push(("Peak RSS", peak_rss_mb, "MB"));
+    }
+    for (metric, value, unit) in metrics {
+        let operation = format!("{} {}", benchmark, metric);
+        results.record_in("resources", &operation, Some(value), unit, Status::Ok, "")?;
+    }
+    Ok(())
+}
 
-    // Calculate average times
-    let total_time =
-        total_file_time + git_op_time + docker_op_time + download_op_time + build_run_op_time + vscode_op_time;
-    let average_time = total_time / 10; // Now counting all 5 file operations + 5 other operations
-    println!("Writing Average Time result...");
-    csv_writer.write_row(&["Average Time", &average_time.to_string()])?;
-    csv_writer.flush()?;
+/// Returns the value of a completed outcome, or writes why the benchmark did not complete.
+fn completed<T>(
+    results: &mut RunResults,
+    operation: &str,
+    outcome: Outcome<T>,
+) -> std::io::Result<Option<T>> {
+    match outcome {
+        Outcome::Completed(value) => Ok(Some(value)),
+        Outcome::Failed(e) => write_failed(results, operation, &e).map(|_| None),
+        Outcome::TimedOut { elapsed, output } => {
+            write_timed_out(results, operation, elapsed, &output).map(|_| None)
+        }
+    }
+}
 
-    // Ensure all data is written to the file
-    csv_writer.flush()?;
+/// Writes a timed outcome, or why it did not complete.
+fn write_outcome(
+    results: &mut RunResults,
+    operation: &str,
+    outcome: Outcome<Duration>,
+) -> std::io::Result<()> {
+    match completed(results, operation, outcome)? {
+        Some(time) => write_result(results, operation, time),
+        None => Ok(()),
+    }
+}
 
-    // Print results to console
-    println!("File Operations:");
-    println!("  Write: {} ms", file_op_results.write_time);
-    println!("  Read: {} ms", file_op_results.read_time);
-    println!("  RAM Load: {} ms", file_op_results.ram_load_time);
-    println!("  Disk Hash: {} ms", file_op_results.disk_hash_time);
-    println!("  RAM Hash: {} ms", file_op_results.ram_hash_time);
-    println!("Git Operation: {} ms", git_op_time);
-    println!("Docker Operation: {} ms", docker_op_time);
-    println!("Download Operation: {} ms", download_op_time);
-    println!("Build and Run Operation: {} ms", build_run_op_time);
-    println!("VS Code Operation: {} ms", vscode_op_time);
-    println!("Average Time: {} ms", average_time);
+/// Stops running benchmarks, keeps the results measured so far and removes
+/// the artifacts before exiting.
+fn handle_interrupt(run_id: &str) -> ! {
+    println!("\nInterrupted, stopping running benchmarks...");
+    process::kill_all();
 
-    println!("Benchmarks completed. Moving results to runs directory...");
+    // Cleanup runs its own commands, which kill_all would refuse
+    process::reset();
+    for runtime in docker_operations::RUNTIMES {
+        DockerOperations::cleanup_run(runtime, run_id);
+    }
+    DockerOperations::cleanup_api_containers();
+
+    let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
+    for extension in ["json", "csv"] {
+        let partial_filename = format!("runs/{}_interrupted.{}", timestamp, extension);
+        match fs::rename(format!("{}.{}", results::RESULTS_PATH, extension), &partial_filename) {
+            Ok(()) => println!("Partial results written to {}", partial_filename),
+            Err(e) => eprintln!("Failed to save partial results: {}", e),
+        }
+    }
+
+    if Path::new(file_operations::TEMP_FILE).exists() {
+        let _ = fs::remove_file(file_operations::TEMP_FILE);
+    }
+    if Path::new("artifacts").exists() {
+        let _ = fs::remove_dir_all("artifacts");
+        println!("Artifacts directory cleaned up.");
+    }
+
+    std::process::exit(130);
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // `benchinator echo-server [address]` serves the remote end of the network benchmark
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("echo-server") {
+        let default_address = format!("127.0.0.1:{}", network_operations::DEFAULT_ECHO_PORT);
+        let address = args.get(2).unwrap_or(&default_address);
+        network_operations::serve(address).await?;
+        return Ok(());
+    }
+
+    println!("Starting benchmarks...");
+
+    // Ensure config, artifacts, and runs directories exist
+    ensure_config_and_directories()?;
+
+    // Identifies everything this run creates outside of the artifacts directory
+    let run_id = format!("{}-{:04x}", Local::now().format("%Y%m%d%H%M%S"), rand::random::<u16>());
+    println!("Run ID: {}", run_id);
+
+    let interrupt_run_id = run_id.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            handle_interrupt(&interrupt_run_id);
+        }
+    });
 
-    // Read config to get run names
+    // Read config to get run names and iterations
     let config_str = fs::read_to_string("config.toml")?;
     let config: Value = toml::from_str(&config_str)
-        .map_err(|e| Box::new(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+        .map_err(|e| Box::new(std::io::Error::other(e)))?;
+    let iterations = config
+        .get("runs")
+        .and_then(|r| r.get("iterations"))
+        .and_then(Value::as_integer)
+        .unwrap_or(1)
+        .max(1) as usize;
+
+    let prerequisites = Prerequisites::check();
+    let timeouts = Timeouts::load()?;
+    let thermal = Thermal::new()?;
+    let noise_monitor = NoiseMonitor::new(&thermal)?;
+    let scoring = Scoring::new()?;
+    let mut results = RunResults::new(&run_id, iterations)?;
+    results.host.print();
+    let security_detector = SecurityDetector::new()?;
+    results.security = security_detector.detect();
+    results.save()?;
+
+    for iteration in 1..=iterations {
+        if iterations > 1 {
+            println!("\nIteration {} of {}", iteration, iterations);
+            reset_artifacts()?;
+        }
+        results.set_iteration(iteration);
+        run_benchmarks(&mut results, &prerequisites, &timeouts, &noise_monitor, &thermal, &run_id).await?;
+    }
+
+    println!("Benchmarks completed. Moving results to runs directory...");
 
     let run_names = config
         .get("runs")
         .and_then(|r| r.get("names"))
         .and_then(|n| n.as_array())
-        .ok_or_else(|| "Run names not found in config")?;
+        .ok_or("Run names not found in config")?;
 
     // Convert run names to Vec<String> for dialog
     let run_options: Vec<String> = run_names
@@ -209,11 +513,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .ok_or("Invalid selection")?;
 
     let run_name = run_options.get(selection).ok_or("Invalid selection")?;
+    if let Some(warning) = security_detector.check_run_type(run_name, &results.security) {
+        results.warn(warning);
+    }
+
+    // Score this run against the runs of the baseline run type so far
+    let score = scoring.score(results.metrics.iter().filter_map(|metric| {
+        let mean = metric.stats.as_ref()?.mean;
+        Some((metric.operation.as_str(), metric.category.as_str(), mean, metric.unit.as_str()))
+    }));
+    match &score {
+        Some(score) => score.print("this run"),
+        None => println!("Not scored: no averages of the baseline run type {} yet", scoring.baseline()),
+    }
+    results.score = score;
+    results.finish(run_name)?;
 
-    // Move results file to runs directory with timestamp and run name
+    // Move results files to runs directory with timestamp and run name
     let timestamp = Local::now().format("%Y%m%d_%H%M%S").to_string();
-    let new_filename = format!("runs/{}_{}.csv", timestamp, run_name);
-    fs::rename("artifacts/benchmark_results.csv", &new_filename)?;
+    let new_filename = format!("runs/{}_{}", timestamp, run_name);
+    for extension in ["json", "csv"] {
+        fs::rename(
+            format!("{}.{}", results::RESULTS_PATH, extension),
+            format!("{}.{}", new_filename, extension),
+        )?;
+    }
 
     // Cleanup
     println!("Cleaning up...");
@@ -222,10 +546,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("Artifacts directory cleaned up.");
     }
 
-    println!("Results written to {}", new_filename);
+    println!("Results written to {}.json and {}.csv", new_filename, new_filename);
 
     // Calculate and update averages for this run type
-    update_run_type_averages(run_name)?;
+    update_run_type_averages(run_name, &scoring)?;
 
     // Generate and save the stacked bar chart
     generate_bar_chart(run_name)?;
@@ -233,10 +557,414 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+/// Empties the artifacts directory except for the results, so every iteration
+/// starts from the same state.
+fn reset_artifacts() -> std::io::Result<()> {
+    for entry in fs::read_dir("artifacts")? {
+        let path = entry?.path();
+        if path.file_stem().is_some_and(|stem| stem == "benchmark_results") {
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs every benchmark once.
+async fn run_benchmarks(
+    results: &mut RunResults,
+    prerequisites: &Prerequisites,
+    timeouts: &Timeouts,
+    noise_monitor: &NoiseMonitor,
+    thermal: &Thermal,
+    run_id: &str,
+) -> std::io::Result<()> {
+    results.set_category("file");
+    cool_down(results, thermal).await;
+    let usage = UsageMeter::start();
+    let sampler = noise_monitor.start();
+    let file_outcome = run_blocking(timeouts.get("file"), || FileOperations::new().perform_operation()).await;
+    results.set_monitoring(sampler.stop());
+    write_resources(results, "File", usage.stop())?;
+    if let Some(file_op_results) = completed(results, "File Operations", file_outcome)? {
+        println!("Writing File Operation results...");
+        write_result(results, "File Write Operation", file_op_results.write_time)?;
+        write_result(results, "File Read Operation", file_op_results.read_time)?;
+        write_result(results, "RAM Load Operation", file_op_results.ram_load_time)?;
+        write_result(results, "Disk Hash Operation", file_op_results.disk_hash_time)?;
+        write_result(results, "RAM Hash Operation", file_op_results.ram_hash_time)?;
+    }
+
+    results.set_category("git");
+    cool_down(results, thermal).await;
+    if let Some(reason) = prerequisites.skip_reason("git") {
+        write_skipped(results, "Git Operation", reason)?;
+    } else {
+        let usage = UsageMeter::start();
+        let sampler = noise_monitor.start();
+        let git_op_time = match GitOperations::new() {
+            Ok(git_ops) => benchmark(timeouts.get("git"), move || git_ops.perform_operation()).await,
+            Err(e) => Outcome::Failed(e),
+        };
+        results.set_monitoring(sampler.stop());
+        write_resources(results, "Git", usage.stop())?;
+        write_outcome(results, "Git Operation", git_op_time)?;
+    }
+
+    results.set_category("docker");
+    cool_down(results, thermal).await;
+    if let Some(reason) = prerequisites.skip_reason("docker") {
+        write_skipped(results, "Docker Operation", reason)?;
+    } else {
+        match DockerOperations::new(run_id) {
+            Ok(docker_ops) if docker_ops.uses_engine_api() => {
+                let usage = UsageMeter::start();
+                let sampler = noise_monitor.start();
+                let docker_outcome =
+                    run_blocking(timeouts.get("docker"), move || docker_ops.perform_api_operation()).await;
+                if matches!(docker_outcome, Outcome::TimedOut { .. }) {
+                    DockerOperations::cleanup_api_containers();
+                }
+                results.set_monitoring(sampler.stop());
+                write_resources(results, "Docker", usage.stop())?;
+                if let Some(api_results) = completed(results, "Docker Operation", docker_outcome)? {
+                    println!("Writing Docker Operation results...");
+                    let runtime = &api_results.runtime;
+                    for (operation, time) in [
+                        ("Docker API Pull Operation", api_results.pull_time),
+                        ("Docker API Create Operation", api_results.create_time),
+                        ("Docker API Start Operation", api_results.start_time),
+                        ("Docker API Run Operation", api_results.wait_time),
+                        ("Docker API Remove Operation", api_results.remove_time),
+                    ] {
+                        write_result_details(results, operation, time, runtime)?;
+                    }
+                }
+            }
+            Ok(docker_ops) => {
+                let docker_ops = Arc::new(docker_ops);
+                let benchmark_ops = Arc::clone(&docker_ops);
+                let usage = UsageMeter::start();
+                let sampler = noise_monitor.start();
+                let docker_outcome =
+                    run_blocking(timeouts.get("docker"), move || benchmark_ops.perform_operation()).await;
+                if matches!(docker_outcome, Outcome::TimedOut { .. }) {
+                    docker_ops.cleanup();
+                }
+                results.set_monitoring(sampler.stop());
+                write_resources(results, "Docker", usage.stop())?;
+                if let Some(docker_results) = completed(results, "Docker Operation", docker_outcome)? {
+                    println!("Writing Docker Operation results...");
+                    let runtime = &docker_results.runtime;
+                    let pull_details = format!("{} from {}", runtime, docker_results.pull_source);
+                    write_result_details(
+                        results,
+                        "Docker Pull Operation",
+                        docker_results.pull_time,
+                        &pull_details,
+                    )?;
+                    if let Some(bytes) = docker_results.pulled_bytes {
+                        let size = bytes as f64 / (1024.0 * 1024.0);
+                        let details = format!("{}, uncompressed", pull_details);
+                        write_metric(results, "Docker Pulled Image Size", size, "MB", &details)?;
+                    }
+                    if let Some(throughput) = docker_results.pull_throughput {
+                        let details = format!("{}, uncompressed image MB/s", pull_details);
+                        write_metric(results, "Docker Pull Throughput", throughput, "MB/s", &details)?;
+                    }
+                    for (operation, time) in [
+                        ("Docker Create Operation", docker_results.create_time),
+                        ("Docker Start Operation", docker_results.start_time),
+                        ("Docker Exec Operation", docker_results.exec_time),
+                        ("Docker Bind Mount I/O Operation", docker_results.bind_mount_io_time),
+                        ("Docker Build Operation", docker_results.build_time),
+                    ] {
+                        write_result_details(results, operation, time, runtime)?;
+                    }
+                }
+            }
+            Err(e) => write_failed(results, "Docker Operation", &e)?,
+        }
+    }
+
+    results.set_category("download");
+    cool_down(results, thermal).await;
+    let usage = UsageMeter::start();
+    let sampler = noise_monitor.start();
+    let download_outcome = match DownloadOperations::new() {
+        Ok(download_ops) => run_async(timeouts.get("download"), download_ops.perform_operation()).await,
+        Err(e) => Outcome::Failed(e),
+    };
+    results.set_monitoring(sampler.stop());
+    write_resources(results, "Download", usage.stop())?;
+    if let Some(download_results) = completed(results, "Download Operation", download_outcome)? {
+        println!("Writing Download Operation results...");
+        for (label, target_results) in download_results.targets {
+            let target_results = match target_results {
+                Ok(target_results) => target_results,
+                Err(e) => {
+                    write_failed(results, &format!("{} Operation", label), &e)?;
+                    continue;
+                }
+            };
+            write_result(results, &format!("{} DNS Operation", label), target_results.dns_time)?;
+            write_result(results, &format!("{} Connect Operation", label), target_results.connect_time)?;
+            if let Some(tls_time) = target_results.tls_time {
+                write_result(results, &format!("{} TLS Handshake Operation", label), tls_time)?;
+            }
+            write_result(
+                results,
+                &format!("{} Time To First Byte", label),
+                target_results.time_to_first_byte,
+            )?;
+            write_result(results, &format!("{} Operation", label), target_results.total_time)?;
+            write_metric(
+                results,
+                &format!("{} Average Throughput", label),
+                target_results.average_throughput,
+                "MB/s",
+                "",
+            )?;
+            write_metric(
+                results,
+                &format!("{} Peak Throughput", label),
+                target_results.peak_throughput,
+                "MB/s",
+                "",
+            )?;
+            let size_details = if target_results.verified_against.is_empty() {
+                "not verified".to_string()
+            } else {
+                format!("verified against {}", target_results.verified_against.join(", "))
+            };
+            write_metric(
+                results,
+                &format!("{} Size", label),
+                target_results.bytes as f64,
+                "bytes",
+                &size_details,
+            )?;
+            match target_results.issued_by_expected_ca {
+                Some(Ok(())) => write_metric(
+                    results,
+                    &format!("{} TLS Inspection", label),
+                    1.0,
+                    "flag",
+                    "chain issued by the expected CA",
+                )?,
+                Some(Err(e)) => write_metric(
+                    results,
+                    &format!("{} TLS Inspection", label),
+                    0.0,
+                    "flag",
+                    &format!("chain not issued by the expected CA: {}", e),
+                )?,
+                None => {}
+            }
+        }
+
+        match download_results.parallel {
+            Some(Ok(parallel)) => {
+                let details = format!("{} concurrent downloads", parallel.downloads);
+                write_result_details(results, "Download Parallel Operation", parallel.total_time, &details)?;
+                write_metric(results, "Download Parallel Throughput", parallel.throughput, "MB/s", &details)?;
+                write_metric(
+                    results,
+                    "Download Parallel Size",
+                    parallel.bytes as f64,
+                    "bytes",
+                    &details,
+                )?;
+            }
+            Some(Err(e)) => write_failed(results, "Download Parallel Operation", &e)?,
+            None => {}
+        }
+    }
+
+    results.set_category("upload");
+    cool_down(results, thermal).await;
+    let usage = UsageMeter::start();
+    let sampler = noise_monitor.start();
+    let upload_outcome = match UploadOperations::new() {
+        Ok(upload_ops) => run_async(timeouts.get("upload"), upload_ops.perform_operation()).await,
+        Err(e) => Outcome::Failed(e),
+    };
+    results.set_monitoring(sampler.stop());
+    write_resources(results, "Upload", usage.stop())?;
+    if let Some(upload_results) = completed(results, "Upload Operation", upload_outcome)? {
+        println!("Writing Upload Operation results...");
+        write_result(results, "Upload Latency", upload_results.latency)?;
+        write_result(results, "Upload Operation", upload_results.total_time)?;
+        write_metric(results, "Upload Throughput", upload_results.throughput, "MB/s", "")?;
+        let size_details = if upload_results.size_verified {
+            "confirmed by the server"
+        } else {
+            "not verified"
+        };
+        write_metric(
+            results,
+            "Upload Size",
+            upload_results.bytes as f64,
+            "bytes",
+            size_details,
+        )?;
+    }
+
+    results.set_category("network");
+    cool_down(results, thermal).await;
+    let usage = UsageMeter::start();
+    let sampler = noise_monitor.start();
+    let network_outcome = match NetworkOperations::new() {
+        Ok(network_ops) => run_async(timeouts.get("network"), network_ops.perform_operation()).await,
+        Err(e) => Outcome::Failed(e),
+    };
+    results.set_monitoring(sampler.stop());
+    write_resources(results, "Network", usage.stop())?;
+    if let Some(network_results) = completed(results, "Network Operations", network_outcome)? {
+        println!("Writing Network Operations results...");
+        for (label, address, link_results) in network_results.links {
+            let link_results = match link_results {
+                Ok(link_results) => link_results,
+                Err(e) => {
+                    write_failed(results, &format!("Network {} Operations", label), &e)?;
+                    continue;
+                }
+            };
+            for (metric, latency) in [
+                ("Connect Latency", &link_results.connect),
+                ("Round Trip Latency", &link_results.round_trip),
+            ] {
+                let details = format!(
+                    "{} (p95 {} over {} samples)",
+                    address,
+                    units::display_duration(latency.p95),
+                    latency.samples
+                );
+                write_metric(
+                    results,
+                    &format!("Network {} {}", label, metric),
+                    units::duration_in(latency.median, "us"),
+                    "us",
+                    &details,
+                )?;
+            }
+            write_metric(
+                results,
+                &format!("Network {} Throughput", label),
+                link_results.throughput,
+                "MB/s",
+                &address,
+            )?;
+        }
+    }
+
+    results.set_category("dns");
+    cool_down(results, thermal).await;
+    let usage = UsageMeter::start();
+    let sampler = noise_monitor.start();
+    let dns_outcome = match DnsOperations::new() {
+        Ok(dns_ops) => run_async(timeouts.get("dns"), dns_ops.perform_operation()).await,
+        Err(e) => Outcome::Failed(e),
+    };
+    results.set_monitoring(sampler.stop());
+    write_resources(results, "DNS", usage.stop())?;
+    if let Some(dns_results) = completed(results, "DNS Operations", dns_outcome)? {
+        println!("Writing DNS Operations results...");
+        let direct = dns_results
+            .direct
+            .map(|(description, direct_results)| ("Direct", description, direct_results));
+        let resolvers = std::iter::once(("System", "the system resolver".to_string(), dns_results.system)).chain(direct);
+        for (label, description, resolver_results) in resolvers {
+            let resolver_results = match resolver_results {
+                Ok(resolver_results) => resolver_results,
+                Err(e) => {
+                    write_failed(results, &format!("DNS {} Lookup", label), &e)?;
+                    continue;
+                }
+            };
+            for (kind, latency) in [("Cold", &resolver_results.cold), ("Warm", &resolver_results.warm)] {
+                let details = format!(
+                    "p95 {} over {} samples from {}",
+                    units::display_duration(latency.p95),
+                    latency.samples,
+                    description
+                );
+                write_metric(
+                    results,
+                    &format!("DNS {} {} Lookup", label, kind),
+                    units::duration_in(latency.median, "us"),
+                    "us",
+                    &details,
+                )?;
+            }
+        }
+    }
+
+    results.set_category("build_run");
+    cool_down(results, thermal).await;
+    if let Some(reason) = prerequisites.skip_reason("build_run") {
+        write_skipped(results, "Build and Run Operation", reason)?;
+    } else {
+        let build_run_ops = BuildRunOperations::new(prerequisites.python());
+        let usage = UsageMeter::start();
+        let sampler = noise_monitor.start();
+        let build_run_op_time =
+            benchmark(timeouts.get("build_run"), move || build_run_ops.perform_operation()).await;
+        results.set_monitoring(sampler.stop());
+        write_resources(results, "Build and Run", usage.stop())?;
+        write_outcome(results, "Build and Run Operation", build_run_op_time)?;
+    }
+
+    results.set_category("vscode");
+    cool_down(results, thermal).await;
+    match (prerequisites.skip_reason("vscode"), VsCodeOperations::new()) {
+        (Some(reason), _) => write_skipped(results, "VS Code Operation", reason)?,
+        (None, Err(e)) => write_failed(results, "VS Code Operation", &e)?,
+        (None, Ok(vscode_ops)) if vscode_ops.headless() => {
+            let usage = UsageMeter::start();
+            let sampler = noise_monitor.start();
+            let vscode_outcome =
+                run_blocking(timeouts.get("vscode"), move || vscode_ops.perform_headless_operation()).await;
+            results.set_monitoring(sampler.stop());
+            write_resources(results, "VS Code", usage.stop())?;
+            if let Some(vscode_results) = completed(results, "VS Code Operation", vscode_outcome)? {
+                println!("Writing VS Code Operation results...");
+                write_result(results, "VS Code CLI Version", vscode_results.version_time)?;
+                write_result(
+                    results,
+                    "VS Code CLI List Extensions",
+                    vscode_results.list_extensions_time,
+                )?;
+                if let Some(install_time) = vscode_results.install_extension_time {
+                    write_result(results, "VS Code CLI Install Extension", install_time)?;
+                }
+                if let Some(ready_time) = vscode_results.ready_time {
+                    write_result(results, "VS Code Time To Ready", ready_time)?;
+                }
+            }
+        }
+        (None, Ok(vscode_ops)) => {
+            let usage = UsageMeter::start();
+            let sampler = noise_monitor.start();
+            let vscode_op_time = benchmark(timeouts.get("vscode"), move || vscode_ops.open_branches()).await;
+            results.set_monitoring(sampler.stop());
+            write_resources(results, "VS Code", usage.stop())?;
+            write_outcome(results, "VS Code Operation", vscode_op_time)?;
+        }
+    }
+
+    Ok(())
+}
+
 // Function to update averages for a specific run type
-fn update_run_type_averages(run_type: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn update_run_type_averages(run_type: &str, scoring: &Scoring) -> Result<(), Box<dyn std::error::Error>> {
     let runs_dir = Path::new("runs");
-    let mut operation_totals: HashMap<String, (f32, u32)> = HashMap::new(); // (sum, count)
+    let mut operation_totals: HashMap<String, (f64, u32)> = HashMap::new(); // (sum, count)
+    let mut operation_info: HashMap<String, (String, String)> = HashMap::new(); // (category, unit)
 
     // Read all CSV files for this run type
     for entry in fs::read_dir(runs_dir)? {
@@ -244,24 +972,28 @@ fn update_run_type_averages(run_type: &str) -> Result<(), Box<dyn std::error::Er
         let path = entry.path();
 
         if path.is_file()
-            && path.extension().map_or(false, |ext| ext == "csv")
+            && path.extension().is_some_and(|ext| ext == "csv")
             && path
                 .file_name()
                 .unwrap()
                 .to_string_lossy()
                 .contains(run_type)
         {
-            let content = fs::read_to_string(&path)?;
-            for line in content.lines().skip(1) {
-                // Skip header
-                let parts: Vec<&str> = line.split(',').collect();
-                if parts.len() == 2 {
-                    let operation = parts[0].trim().to_string();
-                    if let Ok(time) = parts[1].trim().parse::<f32>() {
-                        let (sum, count) = operation_totals.entry(operation).or_insert((0.0, 0));
-                        *sum += time;
-                        *count += 1;
+            for row in csv_writer::read_rows::<ResultRow>(&path)? {
+                if scoring::LEGACY_AGGREGATES.contains(&row.operation.as_str()) {
+                    continue;
+                }
+                // Benchmarks that did not complete have no value
+                if let Some(value) = row.value {
+                    let (category, unit) = operation_info.entry(row.operation.clone()).or_default();
+                    // Files from before categories existed leave it empty
+                    if !row.category.is_empty() {
+                        *category = row.category;
                     }
+                    *unit = row.unit;
+                    let (sum, count) = operation_totals.entry(row.operation).or_insert((0.0, 0));
+                    *sum += value;
+                    *count += 1;
                 }
             }
         }
@@ -271,19 +1003,48 @@ fn update_run_type_averages(run_type: &str) -> Result<(), Box<dyn std::error::Er
     let avg_file_path = format!("avg_{}.csv", run_type);
     let mut csv_writer = CsvWriter::new(&avg_file_path)?;
 
-    // Write header
-    csv_writer.write_row(&["Operation", "Average Time (ms)"])?;
-
-    // Write averages and calculate total
-    let mut total_average = 0.0f32;
+    // Write averages, sorted so that files of different run types line up
+    let mut averages = Vec::new();
     for (operation, (sum, count)) in operation_totals {
-        let average = if count > 0 { sum / count as f32 } else { 0.0 };
-        csv_writer.write_row(&[&operation, &average.to_string()])?;
-        total_average += average;
+        let average = if count > 0 { sum / count as f64 } else { 0.0 };
+        let (category, unit) = operation_info.remove(&operation).unwrap_or_default();
+        averages.push(AverageRow {
+            operation,
+            category,
+            average,
+            display: units::display(average, &unit),
+            unit,
+        });
+    }
+    averages.sort_by(|a, b| (&a.category, &a.operation).cmp(&(&b.category, &b.operation)));
+    for row in &averages {
+        csv_writer.write_row(row)?;
     }
+    // The baseline may be this run type, whose averages are now on disk
+    csv_writer.flush()?;
 
-    // Write total in all caps
-    csv_writer.write_row(&["TOTAL", &total_average.to_string()])?;
+    // Score the averages against the baseline, overall and per category
+    let score = scoring.score(
+        averages
+            .iter()
+            .map(|row| (row.operation.as_str(), row.category.as_str(), row.average, row.unit.as_str())),
+    );
+    if let Some(score) = score {
+        score.print(run_type);
+        let categories = score
+            .categories
+            .iter()
+            .map(|(category, value)| (format!("{} Score", category), *value));
+        for (operation, value) in std::iter::once(("Score".to_string(), score.overall)).chain(categories) {
+            csv_writer.write_row(&AverageRow {
+                operation,
+                category: scoring::SCORE_CATEGORY.to_string(),
+                average: value,
+                unit: "score".to_string(),
+                display: format!("{:.1}", value),
+            })?;
+        }
+    }
     csv_writer.flush()?;
     println!("Updated averages written to {}", avg_file_path);
 
@@ -293,43 +1054,39 @@ fn update_run_type_averages(run_type: &str) -> Result<(), Box<dyn std::error::Er
 fn generate_bar_chart(_run_type: &str) -> Result<(), Box<dyn std::error::Error>> {
     let run_types = ["security_off", "security_on"];
     let mut operations: Vec<String> = Vec::new();
-    let mut run_data: HashMap<String, Vec<f32>> = HashMap::new();
+    let mut run_data: HashMap<String, HashMap<String, f32>> = HashMap::new();
 
-    // Read data from avg_*.csv files
+    // Read data from avg_*.csv files, keyed by operation
     for &rt in &run_types {
         let avg_file = format!("avg_{}.csv", rt);
-        if let Ok(content) = fs::read_to_string(&avg_file) {
-            let mut times = Vec::new();
-            for line in content.lines().skip(1) {  // Skip header
-                let parts: Vec<&str> = line.split(',').collect();
-                if parts.len() == 2 {
-                    let operation = parts[0].trim();
-                    if operation != "TOTAL" {  // Skip total row
-                        if let Ok(time) = parts[1].trim().parse::<f32>() {
-                            if !operations.contains(&operation.to_string()) {
-                                operations.push(operation.to_string());
-                            }
-                            times.push(time);
-                        }
-                    }
+        if let Ok(rows) = csv_writer::read_rows::<AverageRow>(Path::new(&avg_file)) {
+            let mut times = HashMap::new();
+            // The chart only shows benchmark times, without resource usage or scores
+            let benchmark_times = rows.into_iter().filter(|row| {
+                row.unit == "ms"
+                    && row.category != "resources"
+                    && !scoring::LEGACY_AGGREGATES.contains(&row.operation.as_str())
+            });
+            for row in benchmark_times {
+                if !operations.contains(&row.operation) {
+                    operations.push(row.operation.clone());
                 }
+                times.insert(row.operation, row.average as f32);
             }
             run_data.insert(rt.to_string(), times);
         }
     }
 
-    // Prepare data for chart
+    // Prepare data for chart, pairing the runs by operation; operations missing
+    // from either run are left out
+    let empty = HashMap::new();
+    let off_times = run_data.get("security_off").unwrap_or(&empty);
+    let on_times = run_data.get("security_on").unwrap_or(&empty);
+    operations.retain(|op| off_times.contains_key(op) && on_times.contains_key(op));
     let mut chart_data = Vec::new();
     for op in &operations {
-        if let (Some(off_times), Some(on_times)) = (run_data.get("security_off"), run_data.get("security_on")) {
-            if let (Some(&off_time), Some(&on_time)) = (
-                off_times.get(operations.iter().position(|x| x == op).unwrap()),
-                on_times.get(operations.iter().position(|x| x == op).unwrap())
-            ) {
-                chart_data.push((op.as_str(), off_time));
-                chart_data.push((op.as_str(), on_time));
-            }
-        }
+        chart_data.push((op.as_str(), off_times[op]));
+        chart_data.push((op.as_str(), on_times[op]));
     }
 
     // Create scales
@@ -339,9 +1096,16 @@ fn generate_bar_chart(_run_type: &str) -> Result<(), Box<dyn std::error::Error>>
         .set_inner_padding(0.2)
         .set_outer_padding(0.1);
 
-    let max_time = run_data.values()
-        .flat_map(|v| v.iter())
-        .fold(0.0f32, |a, &b| a.max(b));
+    let max_time = chart_data.iter().fold(0.0f32, |a, &(_, b)| a.max(b));
+
+    // Show the times in the unit that suits the longest one
+    let time_unit = units::display_unit(max_time as f64, "ms");
+    let scale = units::convert(1.0, "ms", time_unit).unwrap_or(1.0) as f32;
+    let chart_data: Vec<(&str, f32)> = chart_data
+        .into_iter()
+        .map(|(operation, time)| (operation, time * scale))
+        .collect();
+    let max_time = max_time * scale;
 
     let y = ScaleLinear::new()
         .set_domain(vec![0.0, max_time])
@@ -368,7 +1132,7 @@ fn generate_bar_chart(_run_type: &str) -> Result<(), Box<dyn std::error::Error>>
         .add_view(&view)
         .add_axis_bottom(&x)
         .add_axis_left(&y)
-        .add_left_axis_label("Time (ms)")
+        .add_left_axis_label(format!("Time ({})", time_unit))
         .add_bottom_axis_label("Operations")
         .save(chart_path)?;
 
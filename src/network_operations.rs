@@ -0,0 +1,293 @@
+use crate::download_operations::megabytes_per_second;
+use crate::local_server;
+use serde::Deserialize;
+use std::io;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Port the standalone echo server listens on by default.
+pub const DEFAULT_ECHO_PORT: u16 = 7878;
+
+/// Largest echo message the server accepts; longer frames close the connection,
+/// so a peer cannot make it allocate gigabytes.
+pub const MAX_ECHO_BYTES: u32 = 1024 * 1024;
+
+// Commands of the echo protocol, each followed by a big-endian length
+const ECHO: u8 = b'E';
+const BULK: u8 = b'B';
+
+/// Results for the loopback echo server and the configured remote one.
+pub struct NetworkResults {
+    /// Label of each link, e.g. "Loopback", with the address and its results
+    pub links: Vec<(&'static str, String, Result<LinkResults, String>)>,
+}
+
+pub struct LinkResults {
+    pub connect: Latency,
+    pub round_trip: Latency,
+    /// Bulk transfer throughput in MB/s
+    pub throughput: f64,
+}
+
+/// Latency distribution of a series of samples.
+pub struct Latency {
+    pub median: Duration,
+    pub p95: Duration,
+    pub samples: usize,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    network: NetworkConfig,
+}
+
+#[derive(Deserialize)]
+struct NetworkConfig {
+    #[serde(default = "default_iterations")]
+    iterations: usize,
+    #[serde(default = "default_message_bytes")]
+    message_bytes: u32,
+    #[serde(default = "default_bulk_mb")]
+    bulk_mb: u64,
+    /// `host:port` of a machine running `benchinator echo-server`
+    #[serde(default)]
+    remote: Option<String>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            iterations: default_iterations(),
+            message_bytes: default_message_bytes(),
+            bulk_mb: default_bulk_mb(),
+            remote: None,
+        }
+    }
+}
+
+fn default_iterations() -> usize {
+    100
+}
+
+fn default_message_bytes() -> u32 {
+    64
+}
+
+fn default_bulk_mb() -> u64 {
+    100
+}
+
+pub struct NetworkOperations {
+    config: NetworkConfig,
+}
+
+impl NetworkOperations {
+    pub fn new() -> Result<Self, String> {
+        let config_str = std::fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(NetworkOperations {
+            config: config.network,
+        })
+    }
+
+    pub async fn perform_operation(&self) -> Result<NetworkResults, String> {
+        println!("Performing network operations...");
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("Failed to start echo server: {}", e))?;
+        let loopback = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to start echo server: {}", e))?
+            .to_string();
+        let server = tokio::spawn(run_echo_server(listener));
+
+        let mut links = Vec::new();
+        println!("Measuring loopback to {}...", loopback);
+        links.push(("Loopback", loopback.clone(), self.measure(&loopback).await));
+        server.abort();
+
+        if let Some(remote) = &self.config.remote {
+            println!("Measuring remote link to {}...", remote);
+            links.push(("Remote", remote.clone(), self.measure(remote).await));
+        }
+
+        println!("Network operations completed.");
+        Ok(NetworkResults { links })
+    }
+
+    async fn measure(&self, address: &str) -> Result<LinkResults, String> {
+        let iterations = self.config.iterations.max(1);
+        if self.config.message_bytes > MAX_ECHO_BYTES {
+            return Err(format!(
+                "message_bytes = {} is larger than the echo server accepts ({})",
+                self.config.message_bytes, MAX_ECHO_BYTES
+            ));
+        }
+
+        let mut connects = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            let stream = TcpStream::connect(address)
+                .await
+                .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+            connects.push(start.elapsed());
+            drop(stream);
+        }
+
+        let mut stream = TcpStream::connect(address)
+            .await
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        stream
+            .set_nodelay(true)
+            .map_err(|e| format!("Failed to configure connection to {}: {}", address, e))?;
+
+        let message = vec![0x42u8; self.config.message_bytes as usize];
+        let mut reply = vec![0u8; message.len()];
+        let mut round_trips = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            let start = Instant::now();
+            echo(&mut stream, &message, &mut reply)
+                .await
+                .map_err(|e| format!("Echo to {} failed: {}", address, e))?;
+            round_trips.push(start.elapsed());
+        }
+
+        let bytes = self.config.bulk_mb * 1024 * 1024;
+        let start = Instant::now();
+        let received = bulk(&mut stream, bytes)
+            .await
+            .map_err(|e| format!("Bulk transfer to {} failed: {}", address, e))?;
+        let elapsed = start.elapsed();
+        if received != bytes {
+            return Err(format!("Sent {} bytes to {} but it received {}", bytes, address, received));
+        }
+
+        Ok(LinkResults {
+            connect: Latency::of(connects),
+            round_trip: Latency::of(round_trips),
+            throughput: megabytes_per_second(bytes, elapsed),
+        })
+    }
+}
+
+impl Latency {
+    pub fn of(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        Latency {
+            median: percentile(&samples, 50.0),
+            p95: percentile(&samples, 95.0),
+            samples: samples.len(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty samples.
+pub fn percentile(sorted: &[Duration], percent: f64) -> Duration {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Runs the standalone echo server for `benchinator echo-server [address]`.
+pub async fn serve(address: &str) -> Result<(), String> {
+    let listener = TcpListener::bind(address)
+        .await
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    println!("Echo server listening on {}, press Ctrl-C to stop", address);
+    run_echo_server(listener).await;
+    Ok(())
+}
+
+async fn run_echo_server(listener: TcpListener) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            let _ = handle_connection(stream).await;
+        });
+    }
+}
+
+/// Serves echo and bulk commands until the client disconnects.
+async fn handle_connection(mut stream: TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    loop {
+        let command = match stream.read_u8().await {
+            Ok(command) => command,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        match command {
+            ECHO => {
+                let len = stream.read_u32().await?;
+                if len > MAX_ECHO_BYTES {
+                    return Ok(());
+                }
+                let mut message = vec![0u8; len as usize];
+                stream.read_exact(&mut message).await?;
+                stream.write_all(&message).await?;
+            }
+            BULK => {
+                let len = stream.read_u64().await?;
+                let received = tokio::io::copy(&mut (&mut stream).take(len), &mut tokio::io::sink()).await?;
+                stream.write_u64(received).await?;
+            }
+            _ => return Ok(()),
+        }
+    }
+}
+
+async fn echo(stream: &mut TcpStream, message: &[u8], reply: &mut [u8]) -> io::Result<()> {
+    let mut request = Vec::with_capacity(message.len() + 5);
+    request.push(ECHO);
+    request.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    request.extend_from_slice(message);
+    stream.write_all(&request).await?;
+    stream.read_exact(reply).await?;
+    Ok(())
+}
+
+/// Sends `bytes` of payload and returns how many the server acknowledged.
+async fn bulk(stream: &mut TcpStream, bytes: u64) -> io::Result<u64> {
+    stream.write_u8(BULK).await?;
+    stream.write_u64(bytes).await?;
+    let block = local_server::payload_block();
+    let mut sent = 0u64;
+    while sent < bytes {
+        let len = (bytes - sent).min(block.len() as u64) as usize;
+        stream.write_all(&block[..len]).await?;
+        sent += len as u64;
+    }
+    stream.read_u64().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(run_echo_server(listener));
+        address
+    }
+
+    #[tokio::test]
+    async fn echoes_messages() {
+        let mut stream = TcpStream::connect(start_server().await).await.unwrap();
+        let mut reply = [0u8; 5];
+        echo(&mut stream, b"hello", &mut reply).await.unwrap();
+        assert_eq!(&reply, b"hello");
+    }
+
+    #[tokio::test]
+    async fn closes_connection_on_oversized_message() {
+        let mut stream = TcpStream::connect(start_server().await).await.unwrap();
+        stream.write_u8(ECHO).await.unwrap();
+        stream.write_u32(MAX_ECHO_BYTES + 1).await.unwrap();
+        let mut reply = Vec::new();
+        assert_eq!(stream.read_to_end(&mut reply).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,607 @@
+use crate::resource_usage::{self, ThreadMeter};
+use crate::thermal::{Thermal, ThermalSummary};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Activity on the machine outside the tool while a benchmark ran.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NoiseSummary {
+    pub samples: usize,
+    /// Mean CPU utilisation of the whole machine outside the tool's own processes, in percent
+    pub background_cpu: f64,
+    /// Highest background CPU utilisation between two samples, in percent
+    pub background_cpu_peak: f64,
+    /// Highest 1-minute load average
+    pub load_average: Option<f64>,
+    /// Disk reads and writes outside the tool's own processes, in MB/s
+    pub background_disk: f64,
+    /// Share of the time some task was stalled waiting for memory, in percent
+    pub memory_pressure: Option<f64>,
+    /// Busiest processes outside the tool, as "name (pid 1234) 12.5%" of one CPU
+    pub top_processes: Vec<String>,
+    /// Thresholds the activity exceeded; empty unless the measurement is noisy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    noise: NoiseConfig,
+}
+
+#[derive(Deserialize, Clone)]
+struct NoiseConfig {
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+    #[serde(default = "default_interval_ms")]
+    interval_ms: u64,
+    #[serde(default = "default_top_processes")]
+    top_processes: usize,
+    /// Mean background CPU utilisation in percent above which a measurement is flagged
+    #[serde(default = "default_max_background_cpu")]
+    max_background_cpu: f64,
+    /// Background disk throughput in MB/s above which a measurement is flagged
+    #[serde(default = "default_max_background_disk")]
+    max_background_disk: f64,
+    /// Memory pressure in percent above which a measurement is flagged
+    #[serde(default = "default_max_memory_pressure")]
+    max_memory_pressure: f64,
+}
+
+impl Default for NoiseConfig {
+    fn default() -> Self {
+        NoiseConfig {
+            enabled: default_enabled(),
+            interval_ms: default_interval_ms(),
+            top_processes: default_top_processes(),
+            max_background_cpu: default_max_background_cpu(),
+            max_background_disk: default_max_background_disk(),
+            max_memory_pressure: default_max_memory_pressure(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval_ms() -> u64 {
+    500
+}
+
+fn default_top_processes() -> usize {
+    3
+}
+
+fn default_max_background_cpu() -> f64 {
+    25.0
+}
+
+fn default_max_background_disk() -> f64 {
+    20.0
+}
+
+fn default_max_memory_pressure() -> f64 {
+    10.0
+}
+
+/// What the sampler recorded while a benchmark ran.
+#[derive(Default)]
+pub struct Monitoring {
+    /// Missing when monitoring is disabled or unsupported, or the benchmark was too short
+    pub noise: Option<NoiseSummary>,
+    /// Missing when neither the frequency nor the temperature can be read
+    pub thermal: Option<ThermalSummary>,
+}
+
+pub struct NoiseMonitor {
+    config: NoiseConfig,
+    thermal: Thermal,
+}
+
+impl NoiseMonitor {
+    pub fn new(thermal: &Thermal) -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(NoiseMonitor {
+            config: config.noise,
+            thermal: thermal.clone(),
+        })
+    }
+
+    /// Starts sampling on a background thread until the sampler is stopped. CPU
+    /// frequency and temperature are sampled also when noise monitoring is disabled.
+    pub fn start(&self) -> NoiseSampler {
+        let config = self.config.clone();
+        let thermal = self.thermal.clone();
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            // The sampling is not part of the benchmark's resource usage
+            let meter = ThreadMeter::start();
+            let interval = Duration::from_millis(config.interval_ms.max(50));
+            let mut samples = Vec::new();
+            let mut readings = Vec::new();
+            let mut sample = || {
+                readings.push(thermal.read());
+                if config.enabled {
+                    samples.push(Snapshot::take());
+                }
+            };
+            sample();
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                sample();
+            }
+            sample();
+            let monitoring = Monitoring {
+                noise: summarize(&config, &samples),
+                thermal: ThermalSummary::of(&readings),
+            };
+            meter.exclude();
+            monitoring
+        });
+        NoiseSampler {
+            thread: Some((stop, handle)),
+        }
+    }
+}
+
+pub struct NoiseSampler {
+    thread: Option<(Sender<()>, JoinHandle<Monitoring>)>,
+}
+
+impl NoiseSampler {
+    /// Stops sampling and prints what was recorded.
+    pub fn stop(mut self) -> Monitoring {
+        let Some((stop, handle)) = self.thread.take() else {
+            return Monitoring::default();
+        };
+        let _ = stop.send(());
+        let monitoring = handle.join().unwrap_or_default();
+        if let Some(summary) = &monitoring.noise {
+            summary.print();
+        }
+        if let Some(thermal) = &monitoring.thermal {
+            thermal.print();
+        }
+        monitoring
+    }
+}
+
+impl NoiseSummary {
+    fn print(&self) {
+        let mut line = format!("Background activity: CPU {:.1}%", self.background_cpu);
+        if let Some(load) = self.load_average {
+            line += &format!(", load {:.2}", load);
+        }
+        line += &format!(", disk {:.1} MB/s", self.background_disk);
+        if let Some(pressure) = self.memory_pressure {
+            line += &format!(", memory pressure {:.1}%", pressure);
+        }
+        if !self.top_processes.is_empty() {
+            line += &format!(", top: {}", self.top_processes.join(", "));
+        }
+        println!("{}", line);
+    }
+}
+
+impl Drop for NoiseSampler {
+    fn drop(&mut self) {
+        if let Some((stop, _)) = self.thread.take() {
+            let _ = stop.send(());
+        }
+    }
+}
+
+/// System counters at one point in time.
+struct Snapshot {
+    at: Instant,
+    cpu_busy: u64,
+    cpu_total: u64,
+    /// CPU time of the tool, the container runtime and their descendants, in clock ticks
+    own_cpu: u64,
+    /// Bytes read and written by the tool, the container runtime and their descendants
+    own_io: u64,
+    /// Bytes read and written on all physical disks
+    disk_io: u64,
+    /// Total time some task was stalled on memory, in microseconds
+    memory_stall: Option<u64>,
+    load_average: Option<f64>,
+    /// Name and CPU time of every other process, by pid
+    processes: HashMap<u32, (String, u64)>,
+}
+
+struct ProcessStat {
+    name: String,
+    parent: u32,
+    /// Own CPU time in clock ticks
+    cpu: u64,
+    /// CPU time of children it has waited for
+    children_cpu: u64,
+}
+
+impl Snapshot {
+    fn take() -> Self {
+        let (cpu_busy, cpu_total) = system_cpu();
+        let stats: HashMap<u32, ProcessStat> = process_ids()
+            .into_iter()
+            .filter_map(|pid| Some((pid, process_stat(pid)?)))
+            .collect();
+        let own_tree = own_tree(&stats, std::process::id());
+
+        let mut own_cpu = 0;
+        let mut own_io = 0;
+        let mut processes = HashMap::new();
+        for (pid, stat) in stats {
+            if own_tree.contains(&pid) {
+                own_cpu += stat.cpu + stat.children_cpu;
+                own_io += resource_usage::process_io(&pid.to_string()).map_or(0, |(read, written)| read + written);
+            } else {
+                processes.insert(pid, (stat.name, stat.cpu));
+            }
+        }
+
+        Snapshot {
+            at: Instant::now(),
+            cpu_busy,
+            cpu_total,
+            own_cpu,
+            own_io,
+            disk_io: disk_io(),
+            memory_stall: memory_stall(),
+            load_average: fs::read_to_string("/proc/loadavg")
+                .ok()
+                .and_then(|content| content.split_whitespace().next()?.parse().ok()),
+            processes,
+        }
+    }
+
+    /// Share of all CPUs used outside the tool since `earlier`, in percent.
+    fn background_cpu_since(&self, earlier: &Snapshot) -> f64 {
+        let total = self.cpu_total.saturating_sub(earlier.cpu_total);
+        if total == 0 {
+            return 0.0;
+        }
+        let busy = self.cpu_busy.saturating_sub(earlier.cpu_busy);
+        let own = self.own_cpu.saturating_sub(earlier.own_cpu);
+        busy.saturating_sub(own) as f64 / total as f64 * 100.0
+    }
+}
+
+fn summarize(config: &NoiseConfig, samples: &[Snapshot]) -> Option<NoiseSummary> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    let elapsed = last.at.duration_since(first.at);
+    if last.cpu_total == first.cpu_total {
+        return None;
+    }
+    let seconds = elapsed.as_secs_f64();
+
+    let background_cpu = last.background_cpu_since(first);
+    let background_cpu_peak = samples
+        .windows(2)
+        .map(|pair| pair[1].background_cpu_since(&pair[0]))
+        .fold(0.0, f64::max);
+    let load_average = samples.iter().filter_map(|s| s.load_average).reduce(f64::max);
+    let disk = last.disk_io.saturating_sub(first.disk_io);
+    let own_io = last.own_io.saturating_sub(first.own_io);
+    let background_disk = disk.saturating_sub(own_io) as f64 / (1024.0 * 1024.0) / seconds;
+    let memory_pressure = match (first.memory_stall, last.memory_stall) {
+        (Some(start), Some(end)) => Some(end.saturating_sub(start) as f64 / (seconds * 10_000.0)),
+        _ => None,
+    };
+
+    // Processes that started after the first sample count from zero, and
+    // those that exited before the last one until they were last seen
+    let mut last_seen = HashMap::new();
+    for sample in samples {
+        last_seen.extend(sample.processes.iter());
+    }
+    let ticks = clock_ticks_per_second() * seconds;
+    let mut busiest: Vec<(f64, String)> = last_seen
+        .into_iter()
+        .filter_map(|(pid, (name, cpu))| {
+            let start = first.processes.get(pid).map_or(0, |(_, cpu)| *cpu);
+            let used = cpu.saturating_sub(start);
+            (used > 0).then(|| (used as f64 / ticks * 100.0, format!("{} (pid {})", name, pid)))
+        })
+        .collect();
+    busiest.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let top_processes = busiest
+        .into_iter()
+        .take(config.top_processes)
+        .map(|(percent, process)| format!("{} {:.1}%", process, percent))
+        .collect();
+
+    let mut flags = Vec::new();
+    if background_cpu > config.max_background_cpu {
+        flags.push(format!(
+            "background CPU {:.1}% above {}%",
+            background_cpu, config.max_background_cpu
+        ));
+    }
+    if background_disk > config.max_background_disk {
+        flags.push(format!(
+            "background disk I/O {:.1} MB/s above {} MB/s",
+            background_disk, config.max_background_disk
+        ));
+    }
+    if let Some(pressure) = memory_pressure.filter(|&p| p > config.max_memory_pressure) {
+        flags.push(format!(
+            "memory pressure {:.1}% above {}%",
+            pressure, config.max_memory_pressure
+        ));
+    }
+
+    Some(NoiseSummary {
+        samples: samples.len(),
+        background_cpu,
+        background_cpu_peak,
+        load_average,
+        background_disk,
+        memory_pressure,
+        top_processes,
+        flags,
+    })
+}
+
+/// Busy and total clock ticks of all CPUs from /proc/stat.
+fn system_cpu() -> (u64, u64) {
+    let content = fs::read_to_string("/proc/stat").unwrap_or_default();
+    let Some(line) = content.lines().find(|line| line.starts_with("cpu ")) else {
+        return (0, 0);
+    };
+    // user, nice, system, idle, iowait, irq, softirq, steal; guest time is part of user
+    let ticks: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .take(8)
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    let total: u64 = ticks.iter().sum();
+    let idle = ticks.get(3).unwrap_or(&0) + ticks.get(4).unwrap_or(&0);
+    (total - idle, total)
+}
+
+fn process_ids() -> Vec<u32> {
+    fs::read_dir("/proc")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn process_stat(pid: u32) -> Option<ProcessStat> {
+    parse_stat(&fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?)
+}
+
+fn parse_stat(content: &str) -> Option<ProcessStat> {
+    // The name is in parentheses and may itself contain spaces or parentheses
+    let (head, rest) = content.rsplit_once(')')?;
+    let name = head.split_once('(')?.1.to_string();
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+    Some(ProcessStat {
+        name,
+        parent: field(1)? as u32,
+        cpu: field(11)? + field(12)?,
+        children_cpu: field(13)? + field(14)?,
+    })
+}
+
+/// Daemons and shims of the container runtimes, as they appear in `/proc/<pid>/stat`.
+/// Containers run as their descendants rather than the tool's, but are its work.
+const CONTAINER_RUNTIME: &[&str] = &["dockerd", "containerd", "conmon", "buildkitd"];
+
+fn is_container_runtime(name: &str) -> bool {
+    // containerd-shim-runc-v2 and friends are cut to 15 bytes by the kernel
+    CONTAINER_RUNTIME.contains(&name) || name.starts_with("containerd-shim")
+}
+
+/// The tool's own process, the container runtime and all their descendants.
+fn own_tree(stats: &HashMap<u32, ProcessStat>, own_pid: u32) -> Vec<u32> {
+    let mut tree = vec![own_pid];
+    tree.extend(
+        stats
+            .iter()
+            .filter(|(pid, stat)| **pid != own_pid && is_container_runtime(&stat.name))
+            .map(|(pid, _)| *pid),
+    );
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        let children: Vec<u32> = stats
+            .iter()
+            .filter(|(pid, stat)| stat.parent == parent && !tree.contains(pid))
+            .map(|(pid, _)| *pid)
+            .collect();
+        tree.extend(children);
+        index += 1;
+    }
+    tree
+}
+
+/// Bytes read and written on physical disks, skipping partitions and virtual devices.
+fn disk_io() -> u64 {
+    let content = fs::read_to_string("/proc/diskstats").unwrap_or_default();
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let name = *fields.get(2)?;
+            let virtual_device = ["loop", "ram", "zram", "dm-", "md"].iter().any(|prefix| name.starts_with(prefix));
+            if virtual_device || !std::path::Path::new("/sys/block").join(name).exists() {
+                return None;
+            }
+            let sectors_read: u64 = fields.get(5)?.parse().ok()?;
+            let sectors_written: u64 = fields.get(9)?.parse().ok()?;
+            Some((sectors_read + sectors_written) * 512)
+        })
+        .sum()
+}
+
+fn memory_stall() -> Option<u64> {
+    let content = fs::read_to_string("/proc/pressure/memory").ok()?;
+    let some = content.lines().find(|line| line.starts_with("some "))?;
+    some.split_whitespace()
+        .find_map(|field| field.strip_prefix("total="))?
+        .parse()
+        .ok()
+}
+
+#[cfg(unix)]
+fn clock_ticks_per_second() -> f64 {
+    match unsafe { libc::sysconf(libc::_SC_CLK_TCK) } {
+        ticks if ticks > 0 => ticks as f64,
+        _ => 100.0,
+    }
+}
+
+#[cfg(not(unix))]
+fn clock_ticks_per_second() -> f64 {
+    100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_process_stat() {
+        let stat = parse_stat(
+            "4242 (tmux: server) S 1 4242 4242 0 -1 4194368 1234 0 0 0 150 25 7 3 20 0 1 0 98765 12345678 900 18446744073709551615\n",
+        )
+        .unwrap();
+        assert_eq!(stat.name, "tmux: server");
+        assert_eq!(stat.parent, 1);
+        assert_eq!(stat.cpu, 175);
+        assert_eq!(stat.children_cpu, 10);
+
+        // Names may contain parentheses and spaces of their own
+        let stat = parse_stat("7 (a) (b c)) R 3 7 7 0 -1 0 0 0 0 0 1 2 3 4 20 0 1 0 1 1 1 1").unwrap();
+        assert_eq!(stat.name, "a) (b c)");
+        assert_eq!(stat.parent, 3);
+
+        assert!(parse_stat("").is_none());
+        assert!(parse_stat("12 (short) S 1 12").is_none());
+    }
+
+    fn stat(name: &str, parent: u32) -> ProcessStat {
+        ProcessStat {
+            name: name.to_string(),
+            parent,
+            cpu: 0,
+            children_cpu: 0,
+        }
+    }
+
+    #[test]
+    fn container_runtime_counts_as_own_work() {
+        let stats: HashMap<u32, ProcessStat> = [
+            (1, stat("systemd", 0)),
+            (100, stat("benchinator", 1)),
+            (101, stat("docker", 100)),
+            (200, stat("dockerd", 1)),
+            (201, stat("containerd", 200)),
+            (300, stat("containerd-shim", 1)),
+            (301, stat("nginx", 300)),
+            (302, stat("nginx", 301)),
+            (400, stat("conmon", 1)),
+            (401, stat("postgres", 400)),
+            (500, stat("backup", 1)),
+            (501, stat("gzip", 500)),
+        ]
+        .into_iter()
+        .collect();
+        let mut tree = own_tree(&stats, 100);
+        tree.sort();
+        assert_eq!(tree, vec![100, 101, 200, 201, 300, 301, 302, 400, 401]);
+    }
+
+    fn snapshot(seconds: u64, start: Instant, busy: u64, total: u64, own: u64) -> Snapshot {
+        Snapshot {
+            at: start + Duration::from_secs(seconds),
+            cpu_busy: busy,
+            cpu_total: total,
+            own_cpu: own,
+            own_io: 0,
+            disk_io: 0,
+            memory_stall: None,
+            load_average: None,
+            processes: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn summarizes_background_activity() {
+        let start = Instant::now();
+        let mut first = snapshot(0, start, 0, 0, 0);
+        first.disk_io = 1024 * 1024;
+        first.memory_stall = Some(1_000);
+        first.load_average = Some(0.5);
+        first.processes.insert(10, ("backup".to_string(), 50));
+        // 100 busy ticks of 400, of which 80 were the tool's own: 5% background
+        let mut middle = snapshot(1, start, 100, 400, 80);
+        middle.load_average = Some(2.25);
+        // 300 busy ticks of 400, of which 80 were the tool's own: 55% background
+        let mut last = snapshot(2, start, 400, 800, 160);
+        last.disk_io = 101 * 1024 * 1024;
+        last.own_io = 40 * 1024 * 1024;
+        last.memory_stall = Some(101_000);
+        last.load_average = Some(1.0);
+        last.processes.insert(10, ("backup".to_string(), 250));
+        last.processes.insert(11, ("indexer".to_string(), 20));
+        last.processes.insert(12, ("idle".to_string(), 0));
+
+        let config = NoiseConfig {
+            top_processes: 2,
+            ..NoiseConfig::default()
+        };
+        let summary = summarize(&config, &[first, middle, last]).unwrap();
+        let ticks = clock_ticks_per_second() * 2.0;
+        assert_eq!(summary.samples, 3);
+        assert_eq!(summary.background_cpu, 30.0);
+        assert!((summary.background_cpu_peak - 55.0).abs() < 1e-9);
+        assert_eq!(summary.load_average, Some(2.25));
+        // 100 MB read and written, 40 MB of them by the tool, in 2 seconds
+        assert_eq!(summary.background_disk, 30.0);
+        assert_eq!(summary.memory_pressure, Some(5.0));
+        assert_eq!(
+            summary.top_processes,
+            vec![
+                format!("backup (pid 10) {:.1}%", 200.0 / ticks * 100.0),
+                format!("indexer (pid 11) {:.1}%", 20.0 / ticks * 100.0),
+            ]
+        );
+        assert_eq!(
+            summary.flags,
+            vec![
+                "background CPU 30.0% above 25%".to_string(),
+                "background disk I/O 30.0 MB/s above 20 MB/s".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn quiet_or_too_short_benchmarks() {
+        let start = Instant::now();
+        let config = NoiseConfig::default();
+        assert!(summarize(&config, &[]).is_none());
+        // No clock tick passed between the samples
+        assert!(summarize(&config, &[snapshot(0, start, 5, 10, 0), snapshot(0, start, 5, 10, 0)]).is_none());
+
+        let summary = summarize(&config, &[snapshot(0, start, 0, 0, 0), snapshot(1, start, 100, 400, 90)]).unwrap();
+        assert_eq!(summary.background_cpu, 2.5);
+        assert_eq!(summary.memory_pressure, None);
+        assert!(summary.top_processes.is_empty());
+        assert!(summary.flags.is_empty());
+    }
+}
@@ -0,0 +1,316 @@
+use crate::docker_operations;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// A command-line tool a benchmark shells out to.
+struct Tool {
+    program: &'static str,
+    version_args: &'static [&'static str],
+    min_version: Option<(u32, u32)>,
+}
+
+const GIT: Tool = Tool {
+    program: "git",
+    version_args: &["--version"],
+    min_version: Some((2, 0)),
+};
+
+const DOCKER: Tool = Tool {
+    program: "docker",
+    version_args: &["--version"],
+    min_version: Some((20, 10)),
+};
+
+const PYTHON3: Tool = Tool {
+    program: "python3",
+    version_args: &["--version"],
+    min_version: Some((3, 0)),
+};
+
+const PYTHON: Tool = Tool {
+    program: "python",
+    version_args: &["--version"],
+    min_version: Some((3, 0)),
+};
+
+const PODMAN: Tool = Tool {
+    program: "podman",
+    version_args: &["--version"],
+    min_version: Some((3, 0)),
+};
+
+const NERDCTL: Tool = Tool {
+    program: "nerdctl",
+    version_args: &["--version"],
+    min_version: Some((1, 0)),
+};
+
+const CODE: Tool = Tool {
+    program: "code",
+    version_args: &["--version"],
+    min_version: None,
+};
+
+/// The settings that decide which tools a benchmark needs.
+#[derive(Deserialize, Default)]
+struct Config {
+    #[serde(default)]
+    docker: DockerConfig,
+    #[serde(default)]
+    vscode: VsCodeConfig,
+}
+
+/// Defaults are those of the Docker benchmark: runtime "auto", backend "cli".
+#[derive(Deserialize, Default)]
+struct DockerConfig {
+    #[serde(default)]
+    runtime: Option<String>,
+    #[serde(default)]
+    backend: Option<String>,
+    #[serde(default)]
+    socket: Option<String>,
+}
+
+impl DockerConfig {
+    fn runtime(&self) -> &str {
+        self.runtime.as_deref().unwrap_or("auto")
+    }
+
+    fn backend(&self) -> &str {
+        self.backend.as_deref().unwrap_or("cli")
+    }
+
+    /// Requirements the tools cannot express: the Engine API socket of the api
+    /// backend, or a known runtime name for the cli backend.
+    fn check(&self) -> Result<(), String> {
+        if self.backend() == "api" {
+            let socket = self
+                .socket
+                .clone()
+                .unwrap_or_else(|| docker_operations::default_socket(self.runtime()));
+            if !Path::new(&socket).exists() {
+                return Err(format!("Engine API socket {} does not exist", socket));
+            }
+            println!("  docker: found Engine API socket {}", socket);
+        } else if self.runtime() != "auto" && !docker_operations::RUNTIMES.contains(&self.runtime()) {
+            return Err(format!(
+                "unknown container runtime '{}', expected auto or one of {}",
+                self.runtime(),
+                docker_operations::RUNTIMES.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct VsCodeConfig {
+    /// Defaults to true, as in the VS Code benchmark
+    #[serde(default)]
+    headless: Option<bool>,
+}
+
+/// Benchmarks that depend on external tools, keyed by the name used in `main`.
+/// Each entry lists requirements; a requirement is met by any one of its tools.
+fn benchmarks(config: &Config) -> Vec<(&'static str, Vec<&'static [Tool]>)> {
+    // Only the benchmark that opens windows checks out branches
+    let vscode: Vec<&'static [Tool]> = if config.vscode.headless.unwrap_or(true) {
+        vec![&[CODE]]
+    } else {
+        vec![&[CODE], &[GIT]]
+    };
+    // The cli backend needs the configured runtime, or any one of them with
+    // "auto"; the api backend talks to the socket and needs no CLI
+    let docker: Vec<&'static [Tool]> = match (config.docker.backend(), config.docker.runtime()) {
+        ("api", _) => vec![],
+        (_, "docker") => vec![&[DOCKER]],
+        (_, "podman") => vec![&[PODMAN]],
+        (_, "nerdctl") => vec![&[NERDCTL]],
+        _ => vec![&[DOCKER, PODMAN, NERDCTL]],
+    };
+    vec![
+        ("git", vec![&[GIT]]),
+        ("docker", docker),
+        ("build_run", vec![&[PYTHON3, PYTHON]]),
+        ("vscode", vscode),
+    ]
+}
+
+pub struct Prerequisites {
+    skipped: HashMap<&'static str, String>,
+    /// Programs that passed their check
+    found: HashSet<&'static str>,
+}
+
+impl Prerequisites {
+    /// Checks the tools of every benchmark and prints what was found.
+    pub fn check() -> Self {
+        println!("Checking benchmark prerequisites...");
+
+        // Without a readable config the benchmarks fail on their own, with a clearer error
+        let config: Config = fs::read_to_string("config.toml")
+            .ok()
+            .and_then(|config_str| toml::from_str(&config_str).ok())
+            .unwrap_or_default();
+
+        let mut skipped = HashMap::new();
+        let mut found = HashSet::new();
+        for (benchmark, requirements) in benchmarks(&config) {
+            let mut missing = Vec::new();
+            for alternatives in requirements.iter() {
+                let mut reasons = Vec::new();
+                for tool in alternatives.iter() {
+                    match check_tool(tool) {
+                        Ok(version) => {
+                            println!("  {}: found {} {}", benchmark, tool.program, version);
+                            found.insert(tool.program);
+                            reasons.clear();
+                            break;
+                        }
+                        Err(reason) => reasons.push(reason),
+                    }
+                }
+                if !reasons.is_empty() {
+                    missing.push(reasons.join(", "));
+                }
+            }
+
+            if benchmark == "build_run" && !Path::new("manage.py").exists() {
+                missing.push("manage.py not found in the current directory".to_string());
+            }
+
+            if benchmark == "docker" {
+                if let Err(reason) = config.docker.check() {
+                    missing.push(reason);
+                }
+            }
+
+            if !missing.is_empty() {
+                let reason = missing.join("; ");
+                println!("  {}: will be skipped ({})", benchmark, reason);
+                skipped.insert(benchmark, reason);
+            }
+        }
+
+        Prerequisites { skipped, found }
+    }
+
+    /// Returns why a benchmark cannot run, or `None` when it can.
+    pub fn skip_reason(&self, benchmark: &str) -> Option<&str> {
+        self.skipped.get(benchmark).map(String::as_str)
+    }
+
+    /// The Python 3 interpreter that was found, `python3` or `python`.
+    pub fn python(&self) -> &'static str {
+        python(&self.found)
+    }
+}
+
+fn python(found: &HashSet<&'static str>) -> &'static str {
+    [PYTHON3.program, PYTHON.program]
+        .into_iter()
+        .find(|program| found.contains(program))
+        .unwrap_or(PYTHON3.program)
+}
+
+fn check_tool(tool: &Tool) -> Result<String, String> {
+    let output = Command::new(tool.program)
+        .args(tool.version_args)
+        .output()
+        .map_err(|_| format!("{} is not installed", tool.program))?;
+
+    if !output.status.success() {
+        return Err(format!("{} {} failed", tool.program, tool.version_args.join(" ")));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let version = parse_version(&stdout)
+        .or_else(|| parse_version(&stderr))
+        .ok_or_else(|| format!("could not determine {} version", tool.program))?;
+
+    if let Some((min_major, min_minor)) = tool.min_version {
+        if (version.0, version.1) < (min_major, min_minor) {
+            return Err(format!(
+                "{} {}.{} is older than the required {}.{}",
+                tool.program, version.0, version.1, min_major, min_minor
+            ));
+        }
+    }
+
+    Ok(format!("{}.{}.{}", version.0, version.1, version.2))
+}
+
+/// Finds the first `major.minor[.patch]` version number in a tool's output.
+pub fn parse_version(output: &str) -> Option<(u32, u32, u32)> {
+    output
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find_map(|token| {
+            let mut parts = token.split('.').filter(|p| !p.is_empty());
+            let major = parts.next()?.parse().ok()?;
+            let minor = parts.next()?.parse().ok()?;
+            let patch = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+            Some((major, minor, patch))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(config: &Config, benchmark: &str) -> Vec<Vec<&'static str>> {
+        benchmarks(config)
+            .into_iter()
+            .find(|(name, _)| *name == benchmark)
+            .map(|(_, requirements)| {
+                requirements
+                    .iter()
+                    .map(|tools| tools.iter().map(|tool| tool.program).collect())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn docker_needs_the_configured_runtime() {
+        assert_eq!(
+            programs(&Config::default(), "docker"),
+            vec![vec!["docker", "podman", "nerdctl"]]
+        );
+
+        let podman: Config = toml::from_str("[docker]\nruntime = \"podman\"").unwrap();
+        assert_eq!(programs(&podman, "docker"), vec![vec!["podman"]]);
+
+        let api: Config = toml::from_str("[docker]\nruntime = \"podman\"\nbackend = \"api\"").unwrap();
+        assert!(programs(&api, "docker").is_empty());
+    }
+
+    #[test]
+    fn api_backend_needs_its_socket() {
+        let config: Config =
+            toml::from_str("[docker]\nbackend = \"api\"\nsocket = \"/nonexistent/engine.sock\"").unwrap();
+        assert!(config.docker.check().unwrap_err().contains("/nonexistent/engine.sock"));
+    }
+
+    #[test]
+    fn build_run_takes_python3_or_python() {
+        assert_eq!(programs(&Config::default(), "build_run"), vec![vec!["python3", "python"]]);
+        assert_eq!(python(&HashSet::from(["git", "python3", "python"])), "python3");
+        // Where python is Python 3 and there is no python3, e.g. on Windows
+        assert_eq!(python(&HashSet::from(["python"])), "python");
+        assert_eq!(python(&HashSet::new()), "python3");
+    }
+
+    #[test]
+    fn headless_vscode_does_not_need_git() {
+        let headless: Config = toml::from_str("[vscode]\nheadless = true").unwrap();
+        assert_eq!(programs(&headless, "vscode"), vec![vec!["code"]]);
+        assert_eq!(programs(&Config::default(), "vscode"), vec![vec!["code"]]);
+
+        let windows: Config = toml::from_str("[vscode]\nheadless = false").unwrap();
+        assert_eq!(programs(&windows, "vscode"), vec![vec!["code"], vec!["git"]]);
+    }
+}
@@ -0,0 +1,258 @@
+use serde::Deserialize;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Process group ids of the children that are currently running.
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Set once a benchmark has been cancelled, so it cannot start new children.
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Counts the benchmarks started by `reset`, so the thread of a cancelled
+/// benchmark stays cancelled after the next one has started.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Generation of the benchmark this thread runs, if it runs one
+    static THREAD_GENERATION: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+/// Open Engine API connections by id, shut down by `kill_all` so blocked reads return.
+#[cfg(unix)]
+static CONNECTIONS: Mutex<Vec<(u64, std::os::unix::net::UnixStream)>> = Mutex::new(Vec::new());
+
+#[cfg(unix)]
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    timeouts: Timeouts,
+}
+
+/// Per-benchmark time limits from the `[timeouts]` section, in seconds.
+#[derive(Deserialize)]
+pub struct Timeouts {
+    #[serde(default = "default_timeout_secs")]
+    default_secs: u64,
+    #[serde(flatten)]
+    benchmarks: HashMap<String, u64>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            default_secs: default_timeout_secs(),
+            benchmarks: HashMap::new(),
+        }
+    }
+}
+
+fn default_timeout_secs() -> u64 {
+    600
+}
+
+impl Timeouts {
+    pub fn load() -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(config.timeouts)
+    }
+
+    pub fn get(&self, benchmark: &str) -> Duration {
+        let secs = self.benchmarks.get(benchmark).copied().unwrap_or(self.default_secs);
+        Duration::from_secs(secs)
+    }
+}
+
+/// A child process registered for cancellation until it is waited on or dropped.
+pub struct TrackedChild {
+    child: Option<Child>,
+}
+
+impl TrackedChild {
+    pub fn child_mut(&mut self) -> &mut Child {
+        self.child.as_mut().expect("child already waited on")
+    }
+
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        let child = self.child.take().expect("child already waited on");
+        let pid = child.id();
+        let output = child.wait_with_output();
+        unregister(pid);
+        output
+    }
+}
+
+impl Drop for TrackedChild {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            unregister(child.id());
+        }
+    }
+}
+
+/// A connection registered for cancellation until it is dropped.
+pub struct TrackedConnection {
+    #[cfg(unix)]
+    id: u64,
+}
+
+#[cfg(unix)]
+impl Drop for TrackedConnection {
+    fn drop(&mut self) {
+        CONNECTIONS.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Tracks a connection, so `kill_all` can shut it down while a request is in flight.
+#[cfg(unix)]
+pub fn track_connection(stream: &std::os::unix::net::UnixStream) -> io::Result<TrackedConnection> {
+    let id = NEXT_CONNECTION.fetch_add(1, Ordering::SeqCst);
+    let mut connections = CONNECTIONS.lock().unwrap();
+    // Checked under the lock, so either this sees the flag or kill_all sees the connection
+    if cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "benchmark was cancelled"));
+    }
+    connections.push((id, stream.try_clone()?));
+    Ok(TrackedConnection { id })
+}
+
+#[cfg(not(unix))]
+pub fn track_connection(_stream: &std::net::TcpStream) -> io::Result<TrackedConnection> {
+    if cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "benchmark was cancelled"));
+    }
+    Ok(TrackedConnection {})
+}
+
+/// Starts `command` in its own process group and tracks it, so the whole
+/// process tree can be killed by `kill_all`.
+pub fn spawn(command: &mut Command) -> io::Result<TrackedChild> {
+    if cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "benchmark was cancelled"));
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    let child = command.spawn()?;
+    RUNNING.lock().unwrap().push(child.id());
+    let child = TrackedChild { child: Some(child) };
+    // kill_all may have run between the check and the registration; dropping kills the child
+    if cancelled() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "benchmark was cancelled"));
+    }
+    Ok(child)
+}
+
+/// Tracked replacement for `Command::output`.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    spawn(command)?.wait_with_output()
+}
+
+/// Like `output`, but also runs once the benchmark has been cancelled, to remove
+/// what it left behind. The child is not tracked, so it must finish on its own.
+pub fn cleanup_output(command: &mut Command) -> io::Result<Output> {
+    command.stdin(Stdio::null()).output()
+}
+
+/// Kills every running child process tree, shuts down open connections and
+/// refuses to start new ones until `reset` is called.
+pub fn kill_all() {
+    CANCELLED.store(true, Ordering::SeqCst);
+    let running = RUNNING.lock().unwrap().clone();
+    for pid in running {
+        kill_tree(pid);
+    }
+    #[cfg(unix)]
+    for (_, stream) in CONNECTIONS.lock().unwrap().iter() {
+        let _ = stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// Whether the running benchmark has been cancelled, or the calling thread
+/// belongs to an earlier benchmark. Long loops check it to stop early.
+pub fn cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+        || THREAD_GENERATION.with(|generation| {
+            generation.get().is_some_and(|generation| generation != GENERATION.load(Ordering::SeqCst))
+        })
+}
+
+/// Allows children to be started again after `kill_all`, and returns the
+/// generation of the benchmark that starts now. Threads of earlier benchmarks
+/// stay cancelled.
+pub fn reset() -> u64 {
+    CANCELLED.store(false, Ordering::SeqCst);
+    GENERATION.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+/// Marks the calling thread as running the benchmark of `generation`.
+pub fn enter(generation: u64) {
+    THREAD_GENERATION.with(|current| current.set(Some(generation)));
+}
+
+fn unregister(pid: u32) {
+    RUNNING.lock().unwrap().retain(|&p| p != pid);
+}
+
+/// Kills a process and its descendants.
+#[cfg(unix)]
+pub fn kill_tree(pid: u32) {
+    // The child leads its own process group, so this also reaches its descendants
+    unsafe {
+        libc::kill(-(pid as libc::pid_t), libc::SIGKILL);
+    }
+}
+
+/// Kills a process and its descendants.
+#[cfg(windows)]
+pub fn kill_tree(pid: u32) {
+    let _ = Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .output();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn threads_of_earlier_benchmarks_stay_cancelled() {
+        let generation = reset();
+        let (started, running) = mpsc::channel();
+        let (next_started, next_running) = mpsc::channel();
+        let stale = std::thread::spawn(move || {
+            enter(generation);
+            started.send(cancelled()).unwrap();
+            next_running.recv().unwrap();
+            (cancelled(), spawn(&mut Command::new("true")).is_err())
+        });
+        assert!(!running.recv().unwrap());
+
+        // The next benchmark starts while the thread of this one still runs
+        reset();
+        next_started.send(()).unwrap();
+        assert_eq!(stale.join().unwrap(), (true, true));
+        assert!(!cancelled());
+    }
+}
@@ -0,0 +1,302 @@
+use std::fs;
+use std::sync::Mutex;
+
+/// Usage of the tool's own monitoring threads so far, left out of the benchmarks' usage.
+static EXCLUDED: Mutex<Option<Rusage>> = Mutex::new(None);
+
+/// Resources the tool and the child processes it waited for used during one
+/// benchmark, without the threads that monitor it.
+pub struct ResourceUsage {
+    pub user_cpu_ms: f64,
+    pub system_cpu_ms: f64,
+    /// Part of the CPU time spent in child processes
+    pub children_cpu_ms: f64,
+    /// Highest resident set size of the tool or any child that ended during the benchmark
+    pub peak_rss_mb: Option<f64>,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    /// Bytes read from and written to storage
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+/// Counters at the start of a benchmark.
+pub struct UsageMeter {
+    start: Option<Counters>,
+}
+
+impl UsageMeter {
+    /// Takes the starting counters and resets the tool's peak RSS.
+    pub fn start() -> Self {
+        // Writing 5 resets VmHWM, so the peak below belongs to this benchmark
+        let _ = fs::write("/proc/self/clear_refs", "5");
+        UsageMeter { start: Counters::take() }
+    }
+
+    /// Returns what was used since `start`, or nothing where getrusage is unavailable.
+    pub fn stop(self) -> Option<ResourceUsage> {
+        let start = self.start?;
+        let end = Counters::take()?;
+        let (own, children) = (end.own.since(&start.own), end.children.since(&start.children));
+
+        // The children's maximum covers every child so far; it only belongs to
+        // this benchmark if it grew
+        let children_peak = (end.children.max_rss_kb > start.children.max_rss_kb).then_some(end.children.max_rss_kb);
+        let own_peak = peak_rss_kb().unwrap_or(end.own.max_rss_kb);
+        let peak_rss_mb = own_peak.max(children_peak.unwrap_or(0)) as f64 / 1024.0;
+
+        let (read_bytes, write_bytes) = match (start.io, end.io) {
+            (Some(start), Some(end)) => (end.0.saturating_sub(start.0), end.1.saturating_sub(start.1)),
+            _ => ((own.in_blocks + children.in_blocks) * 512, (own.out_blocks + children.out_blocks) * 512),
+        };
+
+        Some(ResourceUsage {
+            user_cpu_ms: own.user_ms + children.user_ms,
+            system_cpu_ms: own.system_ms + children.system_ms,
+            children_cpu_ms: children.user_ms + children.system_ms,
+            peak_rss_mb: (peak_rss_mb > 0.0).then_some(peak_rss_mb),
+            voluntary_switches: own.voluntary_switches + children.voluntary_switches,
+            involuntary_switches: own.involuntary_switches + children.involuntary_switches,
+            minor_faults: own.minor_faults + children.minor_faults,
+            major_faults: own.major_faults + children.major_faults,
+            read_bytes,
+            write_bytes,
+        })
+    }
+}
+
+/// Measures the calling thread, so that a monitoring thread can leave its own
+/// usage out of the benchmark it watches.
+pub struct ThreadMeter {
+    start: Option<Rusage>,
+}
+
+impl ThreadMeter {
+    pub fn start() -> Self {
+        ThreadMeter {
+            start: Rusage::get(Who::Thread),
+        }
+    }
+
+    /// Adds what the thread used since `start` to the usage excluded from benchmarks.
+    pub fn exclude(self) {
+        let (Some(start), Some(end)) = (self.start, Rusage::get(Who::Thread)) else {
+            return;
+        };
+        let used = end.since(&start);
+        let mut excluded = EXCLUDED.lock().unwrap();
+        *excluded = Some(match excluded.take() {
+            Some(total) => total.plus(&used),
+            None => used,
+        });
+    }
+}
+
+struct Counters {
+    own: Rusage,
+    children: Rusage,
+    /// Storage bytes read and written from /proc/self/io, which includes waited-for children
+    io: Option<(u64, u64)>,
+}
+
+impl Counters {
+    fn take() -> Option<Self> {
+        let own = Rusage::get(Who::Own)?;
+        Some(Counters {
+            own: without_excluded(own, EXCLUDED.lock().unwrap().as_ref()),
+            children: Rusage::get(Who::Children)?,
+            io: process_io("self"),
+        })
+    }
+}
+
+/// The tool's usage without what its monitoring threads used so far.
+fn without_excluded(own: Rusage, excluded: Option<&Rusage>) -> Rusage {
+    match excluded {
+        Some(excluded) => own.since(excluded),
+        None => own,
+    }
+}
+
+enum Who {
+    Own,
+    Children,
+    /// The calling thread only, where the platform can tell
+    Thread,
+}
+
+struct Rusage {
+    user_ms: f64,
+    system_ms: f64,
+    max_rss_kb: u64,
+    minor_faults: u64,
+    major_faults: u64,
+    in_blocks: u64,
+    out_blocks: u64,
+    voluntary_switches: u64,
+    involuntary_switches: u64,
+}
+
+impl Rusage {
+    #[cfg(unix)]
+    fn get(who: Who) -> Option<Self> {
+        let who = match who {
+            Who::Own => libc::RUSAGE_SELF,
+            Who::Children => libc::RUSAGE_CHILDREN,
+            #[cfg(target_os = "linux")]
+            Who::Thread => libc::RUSAGE_THREAD,
+            #[cfg(not(target_os = "linux"))]
+            Who::Thread => return None,
+        };
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+            return None;
+        }
+        let ms = |time: libc::timeval| time.tv_sec as f64 * 1000.0 + time.tv_usec as f64 / 1000.0;
+        Some(Rusage {
+            user_ms: ms(usage.ru_utime),
+            system_ms: ms(usage.ru_stime),
+            // Kilobytes on Linux, bytes on macOS
+            max_rss_kb: if cfg!(target_os = "macos") {
+                usage.ru_maxrss as u64 / 1024
+            } else {
+                usage.ru_maxrss as u64
+            },
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+            in_blocks: usage.ru_inblock as u64,
+            out_blocks: usage.ru_oublock as u64,
+            voluntary_switches: usage.ru_nvcsw as u64,
+            involuntary_switches: usage.ru_nivcsw as u64,
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn get(_who: Who) -> Option<Self> {
+        None
+    }
+
+    fn plus(&self, other: &Rusage) -> Rusage {
+        Rusage {
+            user_ms: self.user_ms + other.user_ms,
+            system_ms: self.system_ms + other.system_ms,
+            max_rss_kb: self.max_rss_kb.max(other.max_rss_kb),
+            minor_faults: self.minor_faults + other.minor_faults,
+            major_faults: self.major_faults + other.major_faults,
+            in_blocks: self.in_blocks + other.in_blocks,
+            out_blocks: self.out_blocks + other.out_blocks,
+            voluntary_switches: self.voluntary_switches + other.voluntary_switches,
+            involuntary_switches: self.involuntary_switches + other.involuntary_switches,
+        }
+    }
+
+    fn since(&self, start: &Rusage) -> Rusage {
+        Rusage {
+            user_ms: (self.user_ms - start.user_ms).max(0.0),
+            system_ms: (self.system_ms - start.system_ms).max(0.0),
+            max_rss_kb: self.max_rss_kb,
+            minor_faults: self.minor_faults.saturating_sub(start.minor_faults),
+            major_faults: self.major_faults.saturating_sub(start.major_faults),
+            in_blocks: self.in_blocks.saturating_sub(start.in_blocks),
+            out_blocks: self.out_blocks.saturating_sub(start.out_blocks),
+            voluntary_switches: self.voluntary_switches.saturating_sub(start.voluntary_switches),
+            involuntary_switches: self.involuntary_switches.saturating_sub(start.involuntary_switches),
+        }
+    }
+}
+
+/// The tool's peak RSS since it was last reset, from VmHWM.
+fn peak_rss_kb() -> Option<u64> {
+    parse_vm_hwm(&fs::read_to_string("/proc/self/status").ok()?)
+}
+
+fn parse_vm_hwm(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Storage bytes read and written by a process, e.g. "self" or a pid, including
+/// the children it has waited for.
+pub fn process_io(process: &str) -> Option<(u64, u64)> {
+    parse_io(&fs::read_to_string(format!("/proc/{}/io", process)).ok()?)
+}
+
+fn parse_io(content: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        content
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+    Some((field("read_bytes:")?, field("write_bytes:")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_storage_bytes_from_proc_io() {
+        let io = "rchar: 323934931\nwchar: 323929600\nsyscr: 632687\nsyscw: 632675\n\
+                  read_bytes: 4096\nwrite_bytes: 323932160\ncancelled_write_bytes: 8192\n";
+        // rchar and cancelled_write_bytes must not be mistaken for the storage bytes
+        assert_eq!(parse_io(io), Some((4096, 323932160)));
+        assert_eq!(parse_io("rchar: 1\nwchar: 2\n"), None);
+        assert_eq!(parse_io("read_bytes: 1\nwrite_bytes: many\n"), None);
+    }
+
+    #[test]
+    fn parses_peak_rss_from_proc_status() {
+        let status = "Name:\tbenchinator\nVmPeak:\t  912344 kB\nVmSize:\t  911320 kB\n\
+                      VmHWM:\t   48212 kB\nVmRSS:\t   40100 kB\n";
+        assert_eq!(parse_vm_hwm(status), Some(48212));
+        assert_eq!(parse_vm_hwm("Name:\tbenchinator\nVmRSS:\t 40100 kB\n"), None);
+    }
+
+    fn rusage(cpu_ms: f64, count: u64, max_rss_kb: u64) -> Rusage {
+        Rusage {
+            user_ms: cpu_ms,
+            system_ms: cpu_ms / 2.0,
+            max_rss_kb,
+            minor_faults: count,
+            major_faults: count,
+            in_blocks: count,
+            out_blocks: count,
+            voluntary_switches: count,
+            involuntary_switches: count,
+        }
+    }
+
+    #[test]
+    fn leaves_out_the_monitoring_threads() {
+        // The sampler of an earlier benchmark used 30 ms, this benchmark's sampler 20 ms
+        let earlier_sampler = rusage(30.0, 3, 0);
+        let excluded = earlier_sampler.plus(&rusage(20.0, 2, 0));
+        assert_eq!(excluded.user_ms, 50.0);
+        assert_eq!(excluded.voluntary_switches, 5);
+
+        let start = without_excluded(rusage(1000.0, 100, 40_000), Some(&earlier_sampler));
+        let end = without_excluded(rusage(1500.0, 150, 48_000), Some(&excluded));
+        let used = end.since(&start);
+        assert_eq!(used.user_ms, 480.0);
+        assert_eq!(used.system_ms, 240.0);
+        assert_eq!(used.minor_faults, 48);
+        assert_eq!(used.involuntary_switches, 48);
+        // The peak is the process's, not a difference
+        assert_eq!(used.max_rss_kb, 48_000);
+
+        assert_eq!(without_excluded(rusage(10.0, 1, 0), None).user_ms, 10.0);
+        // Never below zero, also when the counters are coarser than the thread's
+        let clamped = without_excluded(rusage(10.0, 1, 0), Some(&excluded));
+        assert_eq!(clamped.user_ms, 0.0);
+        assert_eq!(clamped.out_blocks, 0);
+    }
+}
@@ -0,0 +1,356 @@
+use crate::csv_writer::{CsvWriter, ResultRow};
+use crate::host_info::HostInfo;
+use crate::noise_monitor::{Monitoring, NoiseSummary};
+use crate::scoring::Score;
+use crate::security_software::SecurityReport;
+use crate::thermal::ThermalSummary;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io;
+
+/// Where the results of the running benchmark are kept, without extension.
+pub const RESULTS_PATH: &str = "artifacts/benchmark_results";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Skipped,
+    Failed,
+    TimedOut,
+}
+
+/// One measurement of a metric in one iteration.
+#[derive(Serialize, Deserialize)]
+pub struct Sample {
+    pub iteration: usize,
+    /// Missing unless the status is ok
+    pub value: Option<f64>,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub details: String,
+    /// Background activity while the benchmark that produced the sample ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noise: Option<NoiseSummary>,
+    /// CPU frequency and temperature while the benchmark that produced the sample ran
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thermal: Option<ThermalSummary>,
+}
+
+/// Statistics over the values of the successful samples.
+#[derive(Serialize, Deserialize)]
+pub struct Stats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub min: f64,
+    pub max: f64,
+    pub stddev: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Metric {
+    pub operation: String,
+    /// Benchmark the metric belongs to, named like its timeout key, e.g. "download"
+    pub category: String,
+    pub unit: String,
+    /// Ok when every sample is, otherwise the status of the first sample that was not
+    pub status: Status,
+    pub samples: Vec<Sample>,
+    pub stats: Option<Stats>,
+}
+
+/// Everything known about a run; written as JSON, with the CSV derived from it.
+#[derive(Serialize, Deserialize)]
+pub struct RunResults {
+    pub run_id: String,
+    pub run_type: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub tool_version: String,
+    pub iterations: usize,
+    /// Machine the run was measured on, collected when it started
+    pub host: HostInfo,
+    /// Security software found when the run started
+    #[serde(default)]
+    pub security: SecurityReport,
+    /// Problems with the run that do not stop it, such as a run type that contradicts the detected security software
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Contents of config.toml, with passwords removed
+    pub config: Value,
+    pub metrics: Vec<Metric>,
+    /// Comparison of the metric means to the baseline run type, set when the run finishes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<Score>,
+    #[serde(skip)]
+    iteration: usize,
+    #[serde(skip)]
+    category: String,
+    #[serde(skip)]
+    noise: Option<NoiseSummary>,
+    #[serde(skip)]
+    thermal: Option<ThermalSummary>,
+}
+
+impl RunResults {
+    pub fn new(run_id: &str, iterations: usize) -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: toml::Value = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        let mut config = serde_json::to_value(config)
+            .map_err(|e| format!("Failed to convert config: {}", e))?;
+        redact(&mut config);
+
+        Ok(RunResults {
+            run_id: run_id.to_string(),
+            run_type: None,
+            started_at: Local::now().to_rfc3339(),
+            finished_at: None,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            iterations,
+            host: HostInfo::collect(),
+            security: SecurityReport::default(),
+            warnings: Vec::new(),
+            config,
+            metrics: Vec::new(),
+            score: None,
+            iteration: 1,
+            category: String::new(),
+            noise: None,
+            thermal: None,
+        })
+    }
+
+    /// Sets the iteration that the following samples belong to, counting from 1.
+    pub fn set_iteration(&mut self, iteration: usize) {
+        self.iteration = iteration;
+    }
+
+    /// Sets the category of metrics first recorded after this call, and
+    /// forgets the background activity of the previous benchmark.
+    pub fn set_category(&mut self, category: &str) {
+        self.category = category.to_string();
+        self.noise = None;
+        self.thermal = None;
+    }
+
+    /// Category of the running benchmark.
+    pub fn category(&self) -> &str {
+        &self.category
+    }
+
+    /// Sets the background activity and thermal state attached to the following
+    /// samples, warning when the activity was excessive.
+    pub fn set_monitoring(&mut self, monitoring: Monitoring) {
+        let noise = monitoring.noise;
+        if let Some(flags) = noise.as_ref().map(|noise| &noise.flags).filter(|flags| !flags.is_empty()) {
+            let warning = format!(
+                "excessive background activity during the {} benchmark in iteration {}: {}",
+                self.category,
+                self.iteration,
+                flags.join(", ")
+            );
+            self.warn(warning);
+        }
+        self.noise = noise;
+        self.thermal = monitoring.thermal;
+    }
+
+    /// Adds a sample and saves the results, so they survive an interruption.
+    pub fn record(
+        &mut self,
+        operation: &str,
+        value: Option<f64>,
+        unit: &str,
+        status: Status,
+        details: &str,
+    ) -> io::Result<()> {
+        let category = self.category.clone();
+        self.record_in(&category, operation, value, unit, status, details)
+    }
+
+    /// Like `record`, for a metric that belongs to another category than the running benchmark.
+    pub fn record_in(
+        &mut self,
+        category: &str,
+        operation: &str,
+        value: Option<f64>,
+        unit: &str,
+        status: Status,
+        details: &str,
+    ) -> io::Result<()> {
+        let index = match self.metrics.iter().position(|m| m.operation == operation) {
+            Some(index) => index,
+            None => {
+                self.metrics.push(Metric {
+                    operation: operation.to_string(),
+                    category: category.to_string(),
+                    unit: String::new(),
+                    status: Status::Ok,
+                    samples: Vec::new(),
+                    stats: None,
+                });
+                self.metrics.len() - 1
+            }
+        };
+
+        let metric = &mut self.metrics[index];
+        if !unit.is_empty() {
+            metric.unit = unit.to_string();
+        }
+        if metric.status == Status::Ok {
+            metric.status = status;
+        }
+        metric.samples.push(Sample {
+            iteration: self.iteration,
+            value,
+            status,
+            details: details.to_string(),
+            noise: self.noise.clone(),
+            thermal: self.thermal.clone(),
+        });
+        let values: Vec<f64> = metric.samples.iter().filter_map(|s| s.value).collect();
+        metric.stats = Stats::of(values);
+
+        self.save()
+    }
+
+    /// Prints a warning and keeps it with the results.
+    pub fn warn(&mut self, warning: String) {
+        println!("Warning: {}", warning);
+        self.warnings.push(warning);
+    }
+
+    pub fn finish(&mut self, run_type: &str) -> io::Result<()> {
+        self.run_type = Some(run_type.to_string());
+        self.finished_at = Some(Local::now().to_rfc3339());
+        self.save()
+    }
+
+    /// Writes `RESULTS_PATH` as .json and .csv.
+    pub fn save(&self) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(format!("{}.json", RESULTS_PATH), json)?;
+
+        let mut csv_writer = CsvWriter::new(&format!("{}.csv", RESULTS_PATH))?;
+        for metric in &self.metrics {
+            for sample in &metric.samples {
+                csv_writer.write_row(&ResultRow {
+                    operation: metric.operation.clone(),
+                    category: metric.category.clone(),
+                    unit: if sample.value.is_some() { metric.unit.clone() } else { String::new() },
+                    value: sample.value,
+                    iteration: sample.iteration,
+                    status: sample.status,
+                    details: sample.details.clone(),
+                    background_cpu: sample.noise.as_ref().map(|noise| noise.background_cpu),
+                    noise: sample.noise.as_ref().map(|noise| noise.flags.join("; ")).unwrap_or_default(),
+                    cpu_frequency: sample.thermal.as_ref().and_then(|thermal| Some(thermal.cpu_frequency.as_ref()?.mean)),
+                    max_temperature: sample.thermal.as_ref().and_then(|thermal| Some(thermal.temperature.as_ref()?.max)),
+                })?;
+            }
+        }
+        csv_writer.flush()
+    }
+}
+
+impl Stats {
+    fn of(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(f64::total_cmp);
+        let count = values.len();
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let median = if count.is_multiple_of(2) {
+            (values[count / 2 - 1] + values[count / 2]) / 2.0
+        } else {
+            values[count / 2]
+        };
+        let variance = if count > 1 {
+            values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+        Some(Stats {
+            count,
+            mean,
+            median,
+            min: values[0],
+            max: values[count - 1],
+            stddev: variance.sqrt(),
+        })
+    }
+}
+
+/// Formats whole numbers without decimals and everything else with up to six,
+/// which keeps the nanoseconds of a time in milliseconds.
+pub fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value)
+    } else {
+        let formatted = format!("{:.6}", value);
+        formatted.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Removes passwords, including those in proxy URLs, from the config snapshot.
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if key.contains("password") {
+                    *value = Value::String("<redacted>".to_string());
+                } else if let Value::String(text) = value {
+                    if let Ok(mut url) = reqwest::Url::parse(text) {
+                        if url.password().is_some() {
+                            let _ = url.set_password(Some("redacted"));
+                            *text = url.to_string();
+                        }
+                    }
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_of_no_values_is_none() {
+        assert!(Stats::of(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn stats_of_one_value_has_no_spread() {
+        let stats = Stats::of(vec![42.5]).unwrap();
+        assert_eq!((stats.count, stats.mean, stats.median, stats.min, stats.max), (1, 42.5, 42.5, 42.5, 42.5));
+        assert_eq!(stats.stddev, 0.0);
+    }
+
+    #[test]
+    fn stats_of_unsorted_values() {
+        let stats = Stats::of(vec![9.0, 2.0, 5.0, 4.0, 7.0, 4.0, 5.0, 4.0]).unwrap();
+        assert_eq!(stats.count, 8);
+        assert_eq!(stats.mean, 5.0);
+        // Even count: the mean of the two middle values
+        assert_eq!(stats.median, 4.5);
+        assert_eq!((stats.min, stats.max), (2.0, 9.0));
+        // Sample standard deviation, divided by n - 1
+        assert!((stats.stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+
+        let odd = Stats::of(vec![3.0, 1.0, 2.0]).unwrap();
+        assert_eq!(odd.median, 2.0);
+        assert_eq!(odd.stddev, 1.0);
+    }
+}
@@ -0,0 +1,337 @@
+use crate::csv_writer::{self, AverageRow};
+use crate::units;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Category of the score rows in the averages files.
+pub const SCORE_CATEGORY: &str = "score";
+
+/// Aggregates that older versions wrote next to the operations; they are not operations themselves.
+pub const LEGACY_AGGREGATES: &[&str] = &["Average Time", "TOTAL"];
+
+/// How a set of measurements compares to the baseline run type. 100 is as fast
+/// as the baseline; 50 takes twice as long, or reaches half the throughput.
+#[derive(Serialize, Deserialize)]
+pub struct Score {
+    /// Run type whose averages the measurements were compared to
+    pub baseline: String,
+    /// Weighted geometric mean of the category scores
+    pub overall: f64,
+    /// Geometric mean of the metrics of each category
+    pub categories: BTreeMap<String, f64>,
+    /// Number of metrics compared to the baseline
+    pub metrics: usize,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    scoring: ScoringConfig,
+}
+
+#[derive(Deserialize)]
+struct ScoringConfig {
+    /// Run type the others are compared to
+    #[serde(default = "default_baseline")]
+    baseline: String,
+    /// Weight of each category in the overall score; unlisted categories weigh
+    /// 1, except resource usage, which weighs 0
+    #[serde(default)]
+    weights: HashMap<String, f64>,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        ScoringConfig {
+            baseline: default_baseline(),
+            weights: HashMap::new(),
+        }
+    }
+}
+
+fn default_baseline() -> String {
+    "security_off".to_string()
+}
+
+/// The average of an operation in the baseline, in its unit.
+struct Reference {
+    value: f64,
+    unit: String,
+}
+
+pub struct Scoring {
+    config: ScoringConfig,
+}
+
+impl Scoring {
+    pub fn new() -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(Scoring {
+            config: config.scoring,
+        })
+    }
+
+    pub fn baseline(&self) -> &str {
+        &self.config.baseline
+    }
+
+    /// Scores operations given as (operation, category, value, unit) against the
+    /// averages in `avg_<baseline>.csv`. Returns None without a baseline or when
+    /// no operation can be compared.
+    pub fn score<'a>(&self, operations: impl IntoIterator<Item = (&'a str, &'a str, f64, &'a str)>) -> Option<Score> {
+        let path = format!("avg_{}.csv", self.config.baseline);
+        let references: HashMap<String, Reference> = csv_writer::read_rows::<AverageRow>(Path::new(&path))
+            .ok()?
+            .into_iter()
+            .filter(|row| row.category != SCORE_CATEGORY && !LEGACY_AGGREGATES.contains(&row.operation.as_str()))
+            .map(|row| {
+                let reference = Reference {
+                    value: row.average,
+                    unit: row.unit,
+                };
+                (row.operation, reference)
+            })
+            .collect();
+        self.score_against(&references, operations)
+    }
+
+    /// Scores operations against the baseline averages of the same operations.
+    fn score_against<'a>(
+        &self,
+        references: &HashMap<String, Reference>,
+        operations: impl IntoIterator<Item = (&'a str, &'a str, f64, &'a str)>,
+    ) -> Option<Score> {
+        // Natural logarithms of the ratios to the baseline, per category
+        let mut ratios: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+        for (operation, category, value, unit) in operations {
+            // Rows from versions without categories cannot be weighted
+            if category.is_empty() || self.weight(category) <= 0.0 {
+                continue;
+            }
+            let Some(reference) = references.get(operation) else {
+                continue;
+            };
+            let Some(ratio) = ratio(value, unit, reference) else {
+                continue;
+            };
+            ratios.entry(category.to_string()).or_default().push(ratio.ln());
+        }
+
+        let metrics = ratios.values().map(Vec::len).sum();
+        let categories: BTreeMap<String, f64> = ratios
+            .into_iter()
+            .map(|(category, logs)| {
+                let score = 100.0 * (logs.iter().sum::<f64>() / logs.len() as f64).exp();
+                (category, score)
+            })
+            .collect();
+        let total_weight: f64 = categories.keys().map(|category| self.weight(category)).sum();
+        if total_weight <= 0.0 {
+            return None;
+        }
+        let overall = (categories
+            .iter()
+            .map(|(category, score)| self.weight(category) * score.ln())
+            .sum::<f64>()
+            / total_weight)
+            .exp();
+
+        Some(Score {
+            baseline: self.config.baseline.clone(),
+            overall,
+            categories,
+            metrics,
+        })
+    }
+
+    fn weight(&self, category: &str) -> f64 {
+        match self.config.weights.get(category) {
+            Some(weight) => *weight,
+            // Resource usage explains the times, it is not a result of its own
+            None if category == "resources" => 0.0,
+            None => 1.0,
+        }
+    }
+}
+
+impl Score {
+    pub fn print(&self, subject: &str) {
+        let categories: Vec<String> = self
+            .categories
+            .iter()
+            .map(|(category, score)| format!("{} {:.1}", category, score))
+            .collect();
+        println!(
+            "Score of {} against {}: {:.1} ({}; {} metrics)",
+            subject,
+            self.baseline,
+            self.overall,
+            categories.join(", "),
+            self.metrics
+        );
+    }
+}
+
+/// How much better a value is than the baseline: above 1 for shorter times and
+/// higher throughputs. Sizes, counts and other units are not scored.
+fn ratio(value: f64, unit: &str, reference: &Reference) -> Option<f64> {
+    let reference_value = if unit == reference.unit {
+        reference.value
+    } else {
+        units::convert(reference.value, &reference.unit, unit)?
+    };
+    // Zero, negative and non-finite values have no meaningful ratio or logarithm
+    if !(value.is_finite() && value > 0.0 && reference_value.is_finite() && reference_value > 0.0) {
+        return None;
+    }
+    if units::convert(1.0, unit, "ms").is_some() {
+        Some(reference_value / value)
+    } else if units::convert(1.0, unit, "MB/s").is_some() {
+        Some(value / reference_value)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scoring(weights: &[(&str, f64)]) -> Scoring {
+        Scoring {
+            config: ScoringConfig {
+                baseline: "security_off".to_string(),
+                weights: weights.iter().map(|(category, weight)| (category.to_string(), *weight)).collect(),
+            },
+        }
+    }
+
+    fn references(rows: &[(&str, f64, &str)]) -> HashMap<String, Reference> {
+        rows.iter()
+            .map(|(operation, value, unit)| {
+                let reference = Reference {
+                    value: *value,
+                    unit: unit.to_string(),
+                };
+                (operation.to_string(), reference)
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn times_and_throughputs_are_normalised_to_the_baseline() {
+        let references = references(&[("Write", 100.0, "ms"), ("Throughput", 50.0, "MB/s"), ("Clone", 1.0, "s")]);
+        let score = scoring(&[])
+            .score_against(
+                &references,
+                [
+                    ("Write", "file", 200.0, "ms"),
+                    ("Throughput", "download", 100.0, "MB/s"),
+                    ("Clone", "git", 500.0, "ms"),
+                ],
+            )
+            .unwrap();
+        assert_close(score.categories["file"], 50.0);
+        assert_close(score.categories["download"], 200.0);
+        assert_close(score.categories["git"], 200.0);
+        // Geometric mean of 50, 200 and 200
+        assert_close(score.overall, 100.0 * 2f64.powf(1.0 / 3.0));
+        assert_eq!(score.metrics, 3);
+    }
+
+    #[test]
+    fn categories_are_geometric_means_weighted_into_the_overall_score() {
+        let references = references(&[("Read", 10.0, "ms"), ("Write", 10.0, "ms"), ("Push", 10.0, "ms")]);
+        let score = scoring(&[("git", 3.0)])
+            .score_against(
+                &references,
+                [("Read", "file", 5.0, "ms"), ("Write", "file", 20.0, "ms"), ("Push", "git", 5.0, "ms")],
+            )
+            .unwrap();
+        assert_close(score.categories["file"], 100.0);
+        assert_close(score.categories["git"], 200.0);
+        // (1 * ln 100 + 3 * ln 200) / 4
+        assert_close(score.overall, (100f64.ln() / 4.0 + 200f64.ln() * 3.0 / 4.0).exp());
+    }
+
+    #[test]
+    fn missing_operations_and_unscored_units_are_skipped() {
+        let references = references(&[("Write", 100.0, "ms"), ("Size", 10.0, "MB"), ("User CPU Time", 2.0, "ms")]);
+        let score = scoring(&[])
+            .score_against(
+                &references,
+                [
+                    ("Write", "file", 100.0, "ms"),
+                    ("Size", "file", 20.0, "MB"),
+                    ("New Operation", "file", 1.0, "ms"),
+                    ("Write", "", 1.0, "ms"),
+                    // Comparable, but resource usage weighs 0 by default
+                    ("User CPU Time", "resources", 1.0, "ms"),
+                ],
+            )
+            .unwrap();
+        assert_eq!(score.metrics, 1);
+        assert_close(score.overall, 100.0);
+        assert!(!score.categories.contains_key("resources"));
+    }
+
+    #[test]
+    fn resource_usage_is_only_scored_when_weighted_explicitly() {
+        let references = references(&[("Write", 100.0, "ms"), ("User CPU Time", 100.0, "ms")]);
+        let operations = [("Write", "file", 100.0, "ms"), ("User CPU Time", "resources", 50.0, "ms")];
+        // Without weights, and with weights that leave resources out
+        for weights in [&[][..], &[("file", 2.0), ("git", 0.5)][..]] {
+            let score = scoring(weights).score_against(&references, operations).unwrap();
+            assert_eq!(score.metrics, 1);
+            assert_close(score.overall, 100.0);
+            assert!(!score.categories.contains_key("resources"));
+        }
+
+        let score = scoring(&[("resources", 1.0)]).score_against(&references, operations).unwrap();
+        assert_eq!(score.metrics, 2);
+        assert_close(score.categories["resources"], 200.0);
+    }
+
+    #[test]
+    fn a_weights_table_keeps_resource_usage_out() {
+        let config: Config = toml::from_str("[scoring.weights]\ngit = 2.0\n").unwrap();
+        let scoring = Scoring { config: config.scoring };
+        assert_eq!(scoring.weight("git"), 2.0);
+        assert_eq!(scoring.weight("file"), 1.0);
+        assert_eq!(scoring.weight("resources"), 0.0);
+    }
+
+    #[test]
+    fn zero_and_non_finite_values_are_skipped() {
+        let references = references(&[("Write", 100.0, "ms"), ("Read", 0.0, "ms")]);
+        let scoring = scoring(&[]);
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(scoring.score_against(&references, [("Write", "file", value, "ms")]).is_none());
+        }
+        assert!(scoring.score_against(&references, [("Read", "file", 10.0, "ms")]).is_none());
+
+        let score = scoring
+            .score_against(&references, [("Write", "file", f64::NAN, "ms"), ("Write", "file", 50.0, "ms")])
+            .unwrap();
+        assert_close(score.overall, 200.0);
+        assert!(score.overall.is_finite());
+    }
+
+    #[test]
+    fn nothing_comparable_gives_no_score() {
+        let references = references(&[("Write", 100.0, "ms")]);
+        assert!(scoring(&[]).score_against(&references, []).is_none());
+        assert!(scoring(&[("file", 0.0)])
+            .score_against(&references, [("Write", "file", 100.0, "ms")])
+            .is_none());
+    }
+}
@@ -0,0 +1,452 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Security software found running when a run started.
+#[derive(Serialize, Deserialize, Default)]
+pub struct SecurityReport {
+    pub agents: Vec<DetectedAgent>,
+    /// Processes holding a fanotify group, which on-access scanners use to
+    /// intercept file access, as "name (pid 1234)"
+    pub fanotify_users: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DetectedAgent {
+    pub name: String,
+    pub version: Option<String>,
+    /// What gave it away, e.g. "process falcon-sensor (pid 812)"
+    pub evidence: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    security: SecurityConfig,
+}
+
+#[derive(Deserialize)]
+struct SecurityConfig {
+    /// Run types that should only be used while security software is active
+    #[serde(default = "default_active_run_types")]
+    active_run_types: Vec<String>,
+    /// Run types that should only be used while none is active
+    #[serde(default = "default_inactive_run_types")]
+    inactive_run_types: Vec<String>,
+    /// Agents to look for; replaces the built-in list when set
+    #[serde(default = "default_agents")]
+    agents: Vec<AgentConfig>,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        SecurityConfig {
+            active_run_types: default_active_run_types(),
+            inactive_run_types: default_inactive_run_types(),
+            agents: default_agents(),
+        }
+    }
+}
+
+/// How to recognise an agent. Names ending in `*` match as a prefix.
+#[derive(Deserialize)]
+struct AgentConfig {
+    name: String,
+    #[serde(default)]
+    processes: Vec<String>,
+    #[serde(default)]
+    modules: Vec<String>,
+    #[serde(default)]
+    services: Vec<String>,
+    /// dpkg or rpm packages to read the version from
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+fn default_active_run_types() -> Vec<String> {
+    vec!["security_on".to_string()]
+}
+
+fn default_inactive_run_types() -> Vec<String> {
+    vec!["security_off".to_string()]
+}
+
+/// An agent recognised without configuration.
+struct KnownAgent {
+    name: &'static str,
+    processes: &'static [&'static str],
+    modules: &'static [&'static str],
+    services: &'static [&'static str],
+    packages: &'static [&'static str],
+}
+
+const KNOWN_AGENTS: &[KnownAgent] = &[
+    KnownAgent {
+        name: "CrowdStrike Falcon",
+        processes: &["falcon-sensor", "falcond"],
+        modules: &["falcon_*"],
+        services: &["falcon-sensor"],
+        packages: &["falcon-sensor"],
+    },
+    KnownAgent {
+        name: "SentinelOne",
+        processes: &["sentinelone-agent", "s1-agent", "s1-orchestrator"],
+        modules: &[],
+        services: &["sentinelone"],
+        packages: &["sentinelagent"],
+    },
+    KnownAgent {
+        name: "Microsoft Defender for Endpoint",
+        processes: &["wdavdaemon"],
+        modules: &[],
+        services: &["mdatp"],
+        packages: &["mdatp"],
+    },
+    KnownAgent {
+        name: "Sophos",
+        processes: &["sophos_threat_detector", "sophos_managementagent"],
+        modules: &["sophos*"],
+        services: &["sophos-spl"],
+        packages: &[],
+    },
+    KnownAgent {
+        name: "Trend Micro Deep Security",
+        processes: &["ds_agent"],
+        modules: &["dsa_filter", "tmhook"],
+        services: &["ds_agent"],
+        packages: &["ds_agent"],
+    },
+    KnownAgent {
+        name: "Carbon Black",
+        processes: &["cbagentd", "cbsensor"],
+        modules: &["cbsensor*", "event_collector*"],
+        services: &["cbagentd", "cbsensor"],
+        packages: &["cb-psc-sensor"],
+    },
+    KnownAgent {
+        name: "Elastic Defend",
+        processes: &["elastic-endpoint"],
+        modules: &[],
+        services: &["ElasticEndpoint"],
+        packages: &[],
+    },
+    KnownAgent {
+        name: "ClamAV",
+        processes: &["clamd", "clamonacc"],
+        modules: &[],
+        services: &["clamav-daemon", "clamav-clamonacc"],
+        packages: &["clamav-daemon", "clamav"],
+    },
+];
+
+fn default_agents() -> Vec<AgentConfig> {
+    let strings = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+    KNOWN_AGENTS
+        .iter()
+        .map(|agent| AgentConfig {
+            name: agent.name.to_string(),
+            processes: strings(agent.processes),
+            modules: strings(agent.modules),
+            services: strings(agent.services),
+            packages: strings(agent.packages),
+        })
+        .collect()
+}
+
+pub struct SecurityDetector {
+    config: SecurityConfig,
+}
+
+impl SecurityDetector {
+    pub fn new() -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(SecurityDetector {
+            config: config.security,
+        })
+    }
+
+    /// Looks for the configured agents and prints what was found.
+    pub fn detect(&self) -> SecurityReport {
+        println!("Detecting security software...");
+
+        let processes = processes();
+        let modules = loaded_modules();
+        let services = running_services();
+        let fanotify_pids = fanotify_pids();
+
+        let mut report = SecurityReport::default();
+        for agent in &self.config.agents {
+            let mut evidence = Vec::new();
+            for (pid, name) in &processes {
+                if agent.processes.iter().any(|pattern| matches_process(pattern, name)) {
+                    evidence.push(format!("process {} (pid {})", name, pid));
+                    if fanotify_pids.contains(pid) {
+                        evidence.push(format!("fanotify user {} (pid {})", name, pid));
+                    }
+                }
+            }
+            for module in &modules {
+                if agent.modules.iter().any(|pattern| matches(pattern, module)) {
+                    evidence.push(format!("kernel module {}", module));
+                }
+            }
+            for service in &services {
+                if agent.services.iter().any(|pattern| matches(pattern, service)) {
+                    evidence.push(format!("service {}", service));
+                }
+            }
+            if evidence.is_empty() {
+                continue;
+            }
+
+            let version = agent
+                .packages
+                .iter()
+                .find_map(|package| package_version(package))
+                .or_else(|| {
+                    modules
+                        .iter()
+                        .filter(|module| agent.modules.iter().any(|pattern| matches(pattern, module)))
+                        .find_map(|module| read_trimmed(format!("/sys/module/{}/version", module)))
+                });
+            println!(
+                "  {} {}: {}",
+                agent.name,
+                version.as_deref().unwrap_or("(unknown version)"),
+                evidence.join(", ")
+            );
+            report.agents.push(DetectedAgent {
+                name: agent.name.clone(),
+                version,
+                evidence,
+            });
+        }
+
+        report.fanotify_users = processes
+            .iter()
+            .filter(|(pid, _)| fanotify_pids.contains(pid))
+            .map(|(pid, name)| format!("{} (pid {})", name, pid))
+            .collect();
+        if !report.fanotify_users.is_empty() {
+            println!("  fanotify users: {}", report.fanotify_users.join(", "));
+        }
+        if report.agents.is_empty() && report.fanotify_users.is_empty() {
+            println!("  none found");
+        }
+
+        report
+    }
+
+    /// Explains how the selected run type contradicts the report, if it does.
+    pub fn check_run_type(&self, run_type: &str, report: &SecurityReport) -> Option<String> {
+        let found = report
+            .agents
+            .iter()
+            .map(|agent| agent.name.clone())
+            .chain(report.fanotify_users.iter().map(|user| format!("fanotify user {}", user)))
+            .collect::<Vec<_>>();
+        if self.config.inactive_run_types.iter().any(|name| name == run_type) && !found.is_empty() {
+            Some(format!(
+                "run type {} expects no security software, but found {}",
+                run_type,
+                found.join(", ")
+            ))
+        } else if self.config.active_run_types.iter().any(|name| name == run_type) && found.is_empty() {
+            Some(format!(
+                "run type {} expects security software, but none of the configured agents was found",
+                run_type
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// The kernel truncates process names to 15 bytes.
+fn matches_process(pattern: &str, name: &str) -> bool {
+    matches(pattern, name) || (name.len() == 15 && pattern.len() > 15 && pattern.starts_with(name))
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+/// Running processes as (pid, name).
+fn processes() -> Vec<(u32, String)> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut processes: Vec<(u32, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let name = read_trimmed(entry.path().join("comm"))?;
+            Some((pid, name))
+        })
+        .collect();
+    processes.sort();
+    processes
+}
+
+fn loaded_modules() -> Vec<String> {
+    fs::read_to_string("/proc/modules")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(String::from)
+        .collect()
+}
+
+/// Names of running systemd services, without the ".service" suffix.
+fn running_services() -> Vec<String> {
+    let Ok(output) = Command::new("systemctl")
+        .args(["list-units", "--type=service", "--state=running", "--no-legend", "--plain"])
+        .output()
+    else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|unit| unit.trim_end_matches(".service").to_string())
+        .collect()
+}
+
+/// Processes with an open fanotify group. Only processes the tool may inspect,
+/// usually its own user's unless it runs as root, are found.
+fn fanotify_pids() -> HashSet<u32> {
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let fds = fs::read_dir(entry.path().join("fd")).ok()?;
+            fds.flatten()
+                .any(|fd| fs::read_link(fd.path()).is_ok_and(|target| target.as_os_str() == "anon_inode:[fanotify]"))
+                .then_some(pid)
+        })
+        .collect()
+}
+
+fn package_version(package: &str) -> Option<String> {
+    let queries: [(&str, &[&str]); 2] = [
+        ("dpkg-query", &["-W", "-f=${Version}", package]),
+        ("rpm", &["-q", "--qf", "%{VERSION}-%{RELEASE}", package]),
+    ];
+    queries.iter().find_map(|(program, args)| {
+        let output = Command::new(program).args(*args).output().ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !version.is_empty()).then_some(version)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_names_and_prefixes() {
+        let cases = [
+            ("clamd", "clamd", true),
+            ("clamd", "clamdscan", false),
+            ("clamd", "clam", false),
+            ("falcon_*", "falcon_lsm_serviceable", true),
+            ("falcon_*", "falcon_", true),
+            ("falcon_*", "falcon", false),
+            ("sophos*", "sophos", true),
+            ("*", "anything", true),
+            ("cbsensor*", "xcbsensor", false),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(matches(pattern, name), expected, "{} against {}", pattern, name);
+        }
+    }
+
+    #[test]
+    fn matches_process_names_cut_to_15_bytes() {
+        let cases = [
+            // Short names match as they are
+            ("wdavdaemon", "wdavdaemon", true),
+            ("falcon-sensor", "falcon-sensor", true),
+            // The kernel reports only the first 15 bytes of longer names
+            ("sophos_threat_detector", "sophos_threat_d", true),
+            ("sentinelone-agent", "sentinelone-age", true),
+            ("sophos_managementagent", "sophos_manageme", true),
+            // A cut name must be exactly 15 bytes and the start of the pattern
+            ("sophos_threat_detector", "sophos_threat_", false),
+            ("sophos_threat_detector", "sophos_threat_x", false),
+            // Patterns of 15 bytes or less need the whole name
+            ("elastic-endpoin", "elastic-endpoin", true),
+            ("elastic-endpoi", "elastic-endpoin", false),
+            // Prefix patterns still apply
+            ("s1-*", "s1-orchestrator", true),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(matches_process(pattern, name), expected, "{} against {}", pattern, name);
+        }
+    }
+
+    fn report(agents: &[&str], fanotify_users: &[&str]) -> SecurityReport {
+        SecurityReport {
+            agents: agents
+                .iter()
+                .map(|name| DetectedAgent {
+                    name: name.to_string(),
+                    version: None,
+                    evidence: Vec::new(),
+                })
+                .collect(),
+            fanotify_users: fanotify_users.iter().map(|user| user.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn warns_when_the_run_type_contradicts_the_report() {
+        let detector = SecurityDetector {
+            config: SecurityConfig::default(),
+        };
+        let cases = [
+            ("security_off", report(&[], &[]), None),
+            (
+                "security_off",
+                report(&["ClamAV"], &["clamonacc (pid 42)"]),
+                Some("run type security_off expects no security software, but found ClamAV, fanotify user clamonacc (pid 42)"),
+            ),
+            (
+                "security_off",
+                report(&[], &["mystery (pid 7)"]),
+                Some("run type security_off expects no security software, but found fanotify user mystery (pid 7)"),
+            ),
+            ("security_on", report(&["Sophos"], &[]), None),
+            ("security_on", report(&[], &["clamonacc (pid 42)"]), None),
+            (
+                "security_on",
+                report(&[], &[]),
+                Some("run type security_on expects security software, but none of the configured agents was found"),
+            ),
+            // Other run types expect nothing either way
+            ("baseline", report(&[], &[]), None),
+            ("baseline", report(&["Sophos"], &[]), None),
+        ];
+        for (run_type, report, expected) in cases {
+            assert_eq!(detector.check_run_type(run_type, &report).as_deref(), expected, "{}", run_type);
+        }
+    }
+}
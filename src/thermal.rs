@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How often the cool-down phase checks the temperature.
+const COOL_DOWN_POLL: Duration = Duration::from_secs(1);
+
+/// Lowest, mean and highest of a series of samples.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Range {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+/// CPU frequency and temperature while a benchmark ran.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ThermalSummary {
+    /// Mean frequency of all CPUs in MHz
+    pub cpu_frequency: Option<Range>,
+    /// Temperature of the hottest CPU thermal zone in °C
+    pub temperature: Option<Range>,
+}
+
+impl ThermalSummary {
+    /// Summarizes (frequency, temperature) readings; None when neither could be read.
+    pub fn of(readings: &[(Option<f64>, Option<f64>)]) -> Option<Self> {
+        let summary = ThermalSummary {
+            cpu_frequency: Range::of(&readings.iter().filter_map(|r| r.0).collect::<Vec<_>>()),
+            temperature: Range::of(&readings.iter().filter_map(|r| r.1).collect::<Vec<_>>()),
+        };
+        (summary.cpu_frequency.is_some() || summary.temperature.is_some()).then_some(summary)
+    }
+
+    pub fn print(&self) {
+        if let Some(frequency) = &self.cpu_frequency {
+            println!(
+                "CPU frequency: {:.0} MHz (min {:.0}, max {:.0})",
+                frequency.mean, frequency.min, frequency.max
+            );
+        }
+        if let Some(temperature) = &self.temperature {
+            println!(
+                "CPU temperature: {:.1} °C (min {:.1}, max {:.1})",
+                temperature.mean, temperature.min, temperature.max
+            );
+        }
+    }
+}
+
+impl Range {
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        Some(Range {
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    thermal: ThermalConfig,
+}
+
+#[derive(Deserialize, Clone)]
+struct ThermalConfig {
+    /// Types of the thermal zones to read, e.g. "x86_pkg_temp"; empty picks the CPU zones
+    #[serde(default)]
+    zones: Vec<String>,
+    /// Wait before each benchmark until the temperature drops below `cool_down_celsius`
+    #[serde(default)]
+    cool_down: bool,
+    #[serde(default = "default_cool_down_celsius")]
+    cool_down_celsius: f64,
+    #[serde(default = "default_cool_down_timeout_secs")]
+    cool_down_timeout_secs: u64,
+}
+
+impl Default for ThermalConfig {
+    fn default() -> Self {
+        ThermalConfig {
+            zones: Vec::new(),
+            cool_down: false,
+            cool_down_celsius: default_cool_down_celsius(),
+            cool_down_timeout_secs: default_cool_down_timeout_secs(),
+        }
+    }
+}
+
+fn default_cool_down_celsius() -> f64 {
+    60.0
+}
+
+fn default_cool_down_timeout_secs() -> u64 {
+    300
+}
+
+/// Reads CPU temperatures and frequencies from sysfs.
+#[derive(Clone)]
+pub struct Thermal {
+    config: ThermalConfig,
+    /// Temperature files of the selected thermal zones
+    zones: Vec<String>,
+}
+
+impl Thermal {
+    pub fn new() -> Result<Self, String> {
+        let config_str = fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        let zones = thermal_zones(&config.thermal.zones);
+        Ok(Thermal {
+            config: config.thermal,
+            zones,
+        })
+    }
+
+    /// Current CPU frequency and temperature, as summarized by `ThermalSummary::of`.
+    pub fn read(&self) -> (Option<f64>, Option<f64>) {
+        (cpu_frequency(), self.temperature())
+    }
+
+    /// Temperature of the hottest selected thermal zone in °C.
+    pub fn temperature(&self) -> Option<f64> {
+        self.zones
+            .iter()
+            .filter_map(|zone| read_trimmed(zone)?.parse::<f64>().ok())
+            .map(|millidegrees| millidegrees / 1000.0)
+            .reduce(f64::max)
+    }
+
+    /// Waits until the temperature drops below the configured threshold, if the
+    /// cool-down phase is enabled. Fails when it is still too hot at the timeout.
+    pub async fn cool_down(&self) -> Result<(), String> {
+        if !self.config.cool_down {
+            return Ok(());
+        }
+        let Some(mut temperature) = self.temperature() else {
+            return Ok(());
+        };
+        if temperature < self.config.cool_down_celsius {
+            return Ok(());
+        }
+
+        println!(
+            "Cooling down from {:.1} °C to below {:.1} °C...",
+            temperature, self.config.cool_down_celsius
+        );
+        let start = Instant::now();
+        let timeout = Duration::from_secs(self.config.cool_down_timeout_secs);
+        while temperature >= self.config.cool_down_celsius {
+            if start.elapsed() >= timeout {
+                return Err(format!(
+                    "still at {:.1} °C after a {} s cool-down, continuing",
+                    temperature, self.config.cool_down_timeout_secs
+                ));
+            }
+            tokio::time::sleep(COOL_DOWN_POLL).await;
+            temperature = self.temperature().unwrap_or(f64::NEG_INFINITY);
+        }
+        println!("Cooled down to {:.1} °C in {} s", temperature, start.elapsed().as_secs());
+        Ok(())
+    }
+}
+
+/// Mean current frequency of all CPUs in MHz, from cpufreq or else /proc/cpuinfo.
+pub fn cpu_frequency() -> Option<f64> {
+    let cpufreq: Vec<f64> = fs::read_dir("/sys/devices/system/cpu")
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            name.strip_prefix("cpu").is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .filter_map(|entry| read_trimmed(entry.path().join("cpufreq/scaling_cur_freq"))?.parse::<f64>().ok())
+        .map(|kilohertz| kilohertz / 1000.0)
+        .collect();
+    let frequencies = if cpufreq.is_empty() {
+        fs::read_to_string("/proc/cpuinfo")
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| line.starts_with("cpu MHz"))
+            .filter_map(|line| line.split_once(':')?.1.trim().parse::<f64>().ok())
+            .collect()
+    } else {
+        cpufreq
+    };
+    Range::of(&frequencies).map(|range| range.mean)
+}
+
+/// Temperature files of the zones with the given types. Without types, zones
+/// that look like they belong to the CPU are used, or all zones if none does.
+fn thermal_zones(types: &[String]) -> Vec<String> {
+    let Ok(entries) = fs::read_dir("/sys/class/thermal") else {
+        return Vec::new();
+    };
+    let zones: Vec<(String, String)> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .filter_map(|entry| {
+            let zone_type = read_trimmed(entry.path().join("type"))?;
+            Some((zone_type, entry.path().join("temp").to_string_lossy().into_owned()))
+        })
+        .collect();
+    select_zones(zones, types)
+}
+
+/// Picks the temperature files of (type, file) zones as `thermal_zones` describes.
+fn select_zones(zones: Vec<(String, String)>, types: &[String]) -> Vec<String> {
+    let is_cpu = |zone_type: &str| ["cpu", "pkg", "x86", "soc"].iter().any(|part| zone_type.to_lowercase().contains(part));
+    let selected: Vec<String> = zones
+        .iter()
+        .filter(|(zone_type, _)| {
+            if types.is_empty() {
+                is_cpu(zone_type)
+            } else {
+                types.contains(zone_type)
+            }
+        })
+        .map(|(_, temp)| temp.clone())
+        .collect();
+    if selected.is_empty() && types.is_empty() {
+        zones.into_iter().map(|(_, temp)| temp).collect()
+    } else {
+        selected
+    }
+}
+
+fn read_trimmed(path: impl AsRef<Path>) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zones(types: &[&str]) -> Vec<(String, String)> {
+        types
+            .iter()
+            .enumerate()
+            .map(|(index, zone_type)| (zone_type.to_string(), format!("thermal_zone{}/temp", index)))
+            .collect()
+    }
+
+    fn types(types: &[&str]) -> Vec<String> {
+        types.iter().map(|zone_type| zone_type.to_string()).collect()
+    }
+
+    #[test]
+    fn selects_cpu_zones_by_default() {
+        let laptop = zones(&["acpitz", "x86_pkg_temp", "INT3400 Thermal", "TCPU", "iwlwifi_1"]);
+        assert_eq!(select_zones(laptop, &[]), vec!["thermal_zone1/temp", "thermal_zone3/temp"]);
+        let arm = zones(&["soc-thermal", "gpu-thermal"]);
+        assert_eq!(select_zones(arm, &[]), vec!["thermal_zone0/temp"]);
+    }
+
+    #[test]
+    fn falls_back_to_all_zones_without_cpu_zones() {
+        let server = zones(&["acpitz", "pch_cannonlake"]);
+        assert_eq!(select_zones(server, &[]), vec!["thermal_zone0/temp", "thermal_zone1/temp"]);
+        assert!(select_zones(Vec::new(), &[]).is_empty());
+    }
+
+    #[test]
+    fn configured_types_match_exactly() {
+        let laptop = zones(&["acpitz", "x86_pkg_temp", "TCPU"]);
+        assert_eq!(select_zones(laptop.clone(), &types(&["acpitz"])), vec!["thermal_zone0/temp"]);
+        assert_eq!(
+            select_zones(laptop.clone(), &types(&["TCPU", "x86_pkg_temp"])),
+            vec!["thermal_zone1/temp", "thermal_zone2/temp"]
+        );
+        // Configured types that are missing select nothing rather than every zone
+        assert!(select_zones(laptop, &types(&["x86"])).is_empty());
+    }
+
+    #[test]
+    fn summarizes_min_mean_and_max() {
+        assert!(Range::of(&[]).is_none());
+        let range = Range::of(&[2400.0, 3600.0, 1800.0, 4200.0]).unwrap();
+        assert_eq!((range.min, range.mean, range.max), (1800.0, 3000.0, 4200.0));
+
+        let summary = ThermalSummary::of(&[
+            (Some(2000.0), Some(45.0)),
+            (None, Some(55.0)),
+            (Some(3000.0), None),
+            (Some(4000.0), Some(71.5)),
+        ])
+        .unwrap();
+        let frequency = summary.cpu_frequency.unwrap();
+        assert_eq!((frequency.min, frequency.mean, frequency.max), (2000.0, 3000.0, 4000.0));
+        let temperature = summary.temperature.unwrap();
+        assert_eq!((temperature.min, temperature.mean, temperature.max), (45.0, 57.166666666666664, 71.5));
+
+        let summary = ThermalSummary::of(&[(None, Some(40.0))]).unwrap();
+        assert!(summary.cpu_frequency.is_none());
+        assert!(ThermalSummary::of(&[(None, None), (None, None)]).is_none());
+        assert!(ThermalSummary::of(&[]).is_none());
+    }
+
+    #[test]
+    fn reads_the_hottest_zone() {
+        let dir = std::env::temp_dir().join(format!("benchinator-thermal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let zone = |name: &str, content: &str| {
+            let path = dir.join(name);
+            fs::write(&path, content).unwrap();
+            path.to_string_lossy().into_owned()
+        };
+        let thermal = Thermal {
+            config: ThermalConfig::default(),
+            zones: vec![
+                zone("a", "45000\n"),
+                zone("b", "61500\n"),
+                zone("c", "unavailable\n"),
+                dir.join("missing").to_string_lossy().into_owned(),
+            ],
+        };
+        assert_eq!(thermal.temperature(), Some(61.5));
+        let unreadable = Thermal { zones: thermal.zones[2..].to_vec(), ..thermal };
+        assert_eq!(unreadable.temperature(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+/// Units that can be converted into each other, smallest first, with their size
+/// in the smallest unit of the family.
+const FAMILIES: &[&[(&str, f64)]] = &[
+    &[("ns", 1.0), ("us", 1e3), ("ms", 1e6), ("s", 1e9)],
+    &[
+        ("bytes", 1.0),
+        ("KB", 1024.0),
+        ("MB", 1024.0 * 1024.0),
+        ("GB", 1024.0 * 1024.0 * 1024.0),
+    ],
+    &[("KB/s", 1.0), ("MB/s", 1024.0), ("GB/s", 1024.0 * 1024.0)],
+];
+
+fn family(unit: &str) -> Option<&'static [(&'static str, f64)]> {
+    FAMILIES.iter().copied().find(|family| family.iter().any(|(name, _)| *name == unit))
+}
+
+fn size(family: &[(&str, f64)], unit: &str) -> Option<f64> {
+    family.iter().find(|(name, _)| *name == unit).map(|(_, size)| *size)
+}
+
+/// A duration in milliseconds, the unit timed metrics are recorded in.
+pub fn millis(duration: Duration) -> f64 {
+    duration_in(duration, "ms")
+}
+
+/// A duration in a time unit, e.g. "us", keeping its nanoseconds.
+pub fn duration_in(duration: Duration, unit: &str) -> f64 {
+    convert(duration.as_nanos() as f64, "ns", unit).unwrap_or(f64::NAN)
+}
+
+/// Converts a value between units of the same kind, e.g. "us" to "ms".
+pub fn convert(value: f64, from: &str, to: &str) -> Option<f64> {
+    let family = family(from)?;
+    Some(value * size(family, from)? / size(family, to)?)
+}
+
+/// Picks the largest unit of the same kind in which the value is still at least 1,
+/// e.g. "us" for 0.25 ms. Units of other kinds and non-finite values are kept.
+pub fn display_unit(value: f64, unit: &str) -> &str {
+    let Some(family) = family(unit).filter(|_| value.is_finite()) else {
+        return unit;
+    };
+    let base = value.abs() * size(family, unit).unwrap_or(1.0);
+    family
+        .iter()
+        .rev()
+        .find(|(_, size)| base >= *size)
+        .map_or(family[0].0, |(name, _)| name)
+}
+
+/// Formats a value with three significant digits in its display unit, e.g. "250 us".
+pub fn display(value: f64, unit: &str) -> String {
+    let display_unit = display_unit(value, unit);
+    let scaled = convert(value, unit, display_unit).unwrap_or(value);
+    if scaled.fract() == 0.0 {
+        return format!("{} {}", scaled, display_unit);
+    }
+    let decimals = match scaled.abs() {
+        v if v >= 100.0 || v == 0.0 => 0,
+        v if v >= 10.0 => 1,
+        _ => 2,
+    };
+    format!("{:.*} {}", decimals, scaled, display_unit)
+}
+
+/// Formats a duration in its display unit, e.g. "1.25 s".
+pub fn display_duration(duration: Duration) -> String {
+    display(millis(duration), "ms")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_picks_the_unit_that_reads_best() {
+        assert_eq!(display(0.25, "ms"), "250 us");
+        assert_eq!(display(0.000384, "ms"), "384 ns");
+        assert_eq!(display(1250.0, "ms"), "1.25 s");
+        assert_eq!(display(42800.0, "ms"), "42.8 s");
+        assert_eq!(display(12.5, "us"), "12.5 us");
+        assert_eq!(display(1536.0, "bytes"), "1.50 KB");
+        assert_eq!(display(2048.0, "KB/s"), "2 MB/s");
+        assert_eq!(display(-1500.0, "ms"), "-1.50 s");
+    }
+
+    #[test]
+    fn display_keeps_unknown_units_and_odd_values() {
+        assert_eq!(display(2.345, "%"), "2.35 %");
+        assert_eq!(display(0.0, "ms"), "0 ns");
+        assert_eq!(display(f64::NAN, "ms"), "NaN ms");
+        assert_eq!(display(f64::INFINITY, "MB/s"), "inf MB/s");
+    }
+
+    #[test]
+    fn converts_within_a_family_only() {
+        assert_eq!(convert(1500.0, "us", "ms"), Some(1.5));
+        assert_eq!(convert(1.0, "GB", "MB"), Some(1024.0));
+        assert_eq!(convert(1.0, "ms", "MB"), None);
+        assert_eq!(convert(1.0, "ms", "%"), None);
+        assert_eq!(duration_in(Duration::from_nanos(1_500), "us"), 1.5);
+        assert_eq!(millis(Duration::from_micros(2_500)), 2.5);
+        assert_eq!(display_duration(Duration::from_millis(1_250)), "1.25 s");
+    }
+}
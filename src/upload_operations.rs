@@ -0,0 +1,246 @@
+use crate::download_operations::megabytes_per_second;
+use crate::http_client::HttpConfig;
+use crate::local_server::{self, LocalServer, LocalServerConfig};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+/// Latency and transfer metrics of an upload.
+pub struct UploadResults {
+    /// Round trip of an empty request to the same endpoint
+    pub latency: Duration,
+    /// Time from sending the request until the response arrived
+    pub total_time: Duration,
+    pub bytes: u64,
+    /// Whether the server confirmed how many bytes it received
+    pub size_verified: bool,
+    /// Throughput in MB/s
+    pub throughput: f64,
+}
+
+#[derive(Deserialize)]
+struct Config {
+    #[serde(default)]
+    upload: UploadConfig,
+}
+
+#[derive(Deserialize)]
+struct UploadConfig {
+    /// "local" uploads to the embedded server, "remote" to `url`
+    #[serde(default = "default_source")]
+    source: String,
+    #[serde(default)]
+    url: String,
+    /// POST or PUT
+    #[serde(default = "default_method")]
+    method: String,
+    #[serde(default = "default_size_mb")]
+    size_mb: u64,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        UploadConfig {
+            source: default_source(),
+            url: String::new(),
+            method: default_method(),
+            size_mb: default_size_mb(),
+        }
+    }
+}
+
+fn default_source() -> String {
+    "local".to_string()
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_size_mb() -> u64 {
+    50
+}
+
+pub struct UploadOperations {
+    config: UploadConfig,
+}
+
+impl UploadOperations {
+    pub fn new() -> Result<Self, String> {
+        let config_str = std::fs::read_to_string("config.toml")
+            .map_err(|e| format!("Failed to read config file: {}", e))?;
+        let config: Config = toml::from_str(&config_str)
+            .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(UploadOperations {
+            config: config.upload,
+        })
+    }
+
+    pub async fn perform_operation(&self) -> Result<UploadResults, String> {
+        println!("Performing upload operation...");
+
+        let upload = &self.config;
+        let method = match upload.method.to_ascii_uppercase().as_str() {
+            "POST" => reqwest::Method::POST,
+            "PUT" => reqwest::Method::PUT,
+            other => return Err(format!("Unsupported upload method '{}', expected POST or PUT", other)),
+        };
+
+        // The server has to outlive the upload, so it is kept here
+        let (_server, url, client) = match upload.source.as_str() {
+            "local" => {
+                let server = LocalServer::start(&LocalServerConfig::load()?).await?;
+                let url = server.url("upload");
+                let client = reqwest::Client::builder()
+                    .no_proxy()
+                    .danger_accept_invalid_certs(true)
+                    .build()
+                    .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+                (Some(server), url, client)
+            }
+            "remote" => {
+                if upload.url.is_empty() {
+                    return Err("Upload source is remote but no url is configured".to_string());
+                }
+                (None, upload.url.clone(), HttpConfig::load()?.client()?)
+            }
+            other => {
+                return Err(format!(
+                    "Unknown upload source '{}', expected local or remote",
+                    other
+                ))
+            }
+        };
+
+        let latency_start = Instant::now();
+        client
+            .request(method.clone(), &url)
+            .body(Vec::new())
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| format!("Failed to {} to {}: {}", method, url, e))?;
+        let latency = latency_start.elapsed();
+
+        let payload = payload(upload.size_mb * 1024 * 1024);
+        let bytes = payload.len() as u64;
+
+        println!("Uploading {} bytes to {}...", bytes, url);
+        // The embedded server answers with the number of bytes it received
+        let size_verified = upload.source == "local";
+        let elapsed = send(&client, method, &url, payload, size_verified).await?;
+
+        println!("File uploaded successfully.");
+        Ok(UploadResults {
+            latency,
+            total_time: elapsed,
+            bytes,
+            size_verified,
+            throughput: megabytes_per_second(bytes, elapsed),
+        })
+    }
+}
+
+/// Uploads `payload` and returns the time until the response arrived. With
+/// `size_verified`, the response must be the number of bytes received.
+async fn send(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: &str,
+    payload: Vec<u8>,
+    size_verified: bool,
+) -> Result<Duration, String> {
+    let bytes = payload.len();
+    let start = Instant::now();
+    let response = client
+        .request(method.clone(), url)
+        .body(payload)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(|e| format!("Failed to {} to {}: {}", method, url, e))?;
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response from {}: {}", url, e))?;
+    let elapsed = start.elapsed();
+
+    if size_verified && body.trim() != bytes.to_string() {
+        return Err(format!(
+            "Uploaded {} bytes but the server received {}",
+            bytes,
+            body.trim()
+        ));
+    }
+    Ok(elapsed)
+}
+
+/// Builds `size` bytes of the same data the local server serves.
+fn payload(size: u64) -> Vec<u8> {
+    let block = local_server::payload_block();
+    block.iter().copied().cycle().take(size as usize).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A server that reads one request and answers that it received `received` bytes.
+    async fn miscounting_server(received: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/upload", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            // Headers and the 1000 byte body
+            let complete = |request: &[u8]| {
+                let headers = request.windows(4).position(|window| window == b"\r\n\r\n");
+                headers.is_some_and(|end| request.len() >= end + 4 + 1000)
+            };
+            while !complete(&request) {
+                let read = stream.read(&mut buffer).await.unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                received.len(),
+                received
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    fn client() -> reqwest::Client {
+        reqwest::Client::builder().no_proxy().build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn wrong_byte_count_is_an_error() {
+        let url = miscounting_server("999").await;
+        let error = send(&client(), reqwest::Method::POST, &url, payload(1000), true)
+            .await
+            .unwrap_err();
+        assert_eq!(error, "Uploaded 1000 bytes but the server received 999");
+    }
+
+    #[tokio::test]
+    async fn byte_count_is_only_checked_when_the_server_reports_it() {
+        let url = miscounting_server("thanks").await;
+        assert!(send(&client(), reqwest::Method::PUT, &url, payload(1000), false).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn embedded_server_confirms_the_upload() {
+        let server = LocalServer::start(&LocalServerConfig::default()).await.unwrap();
+        let url = server.url("upload");
+        for method in [reqwest::Method::POST, reqwest::Method::PUT] {
+            assert!(send(&client(), method, &url, payload(100_000), true).await.is_ok());
+        }
+    }
+}
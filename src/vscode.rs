@@ -1,11 +1,25 @@
 use std::fs;
 use std::process::Command;
+use crate::process;
+use std::time::{Duration, Instant};
+use crate::units;
 use serde::Deserialize;
 use std::path::Path;
 
+const USER_DATA_DIR: &str = "artifacts/vscode-user-data";
+const EXTENSIONS_DIR: &str = "artifacts/vscode-extensions";
+const WORKSPACE_DIR: &str = "artifacts/vscode-workspace";
+
+/// How often `code --status` is asked whether the extension host runs.
+const READY_POLL: Duration = Duration::from_millis(200);
+
 #[derive(Deserialize)]
 struct Config {
-    git: GitConfig,
+    /// Only needed when opening windows, which checks out the branches
+    #[serde(default)]
+    git: Option<GitConfig>,
+    #[serde(default)]
+    vscode: VsCodeConfig,
 }
 
 #[derive(Deserialize)]
@@ -13,24 +27,187 @@ struct GitConfig {
     branches: Vec<String>,
 }
 
-pub struct VsCodeOperations;
+#[derive(Deserialize)]
+struct VsCodeConfig {
+    #[serde(default = "default_headless")]
+    headless: bool,
+    #[serde(default)]
+    vsix: Option<String>,
+    /// Longest wait for a launched window's extension host
+    #[serde(default = "default_ready_timeout_secs")]
+    ready_timeout_secs: u64,
+}
 
-impl VsCodeOperations {
-    pub fn new() -> Self {
-        VsCodeOperations
+impl Default for VsCodeConfig {
+    fn default() -> Self {
+        VsCodeConfig {
+            headless: default_headless(),
+            vsix: None,
+            ready_timeout_secs: default_ready_timeout_secs(),
+        }
     }
+}
 
-    pub fn open_branches(&self) -> Result<(), String> {
-        // Read config file
+fn default_headless() -> bool {
+    true
+}
+
+fn default_ready_timeout_secs() -> u64 {
+    60
+}
+
+pub struct VsCodeResults {
+    pub version_time: Duration,
+    pub list_extensions_time: Duration,
+    pub install_extension_time: Option<Duration>,
+    /// From launching a window until its extension host runs; None without a display
+    pub ready_time: Option<Duration>,
+}
+
+pub struct VsCodeOperations {
+    config: Config,
+}
+
+impl VsCodeOperations {
+    pub fn new() -> Result<Self, String> {
         let config_str = fs::read_to_string("config.toml")
             .map_err(|e| format!("Failed to read config file: {}", e))?;
         let config: Config = toml::from_str(&config_str)
             .map_err(|e| format!("Failed to parse config file: {}", e))?;
+        Ok(VsCodeOperations { config })
+    }
+
+    pub fn headless(&self) -> bool {
+        self.config.vscode.headless
+    }
+
+    /// Times the `code` CLI against an isolated user data and extensions
+    /// directory, then how long a window on an empty workspace takes until
+    /// its extension host runs.
+    pub fn perform_headless_operation(&self) -> Result<VsCodeResults, String> {
+        println!("Performing headless VS Code operations...");
+
+        for dir in [USER_DATA_DIR, EXTENSIONS_DIR] {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create directory '{}': {}", dir, e))?;
+        }
+
+        // CLI start without loading the editor
+        let version_start = Instant::now();
+        self.run_code(&["--version"])?;
+        let version_time = version_start.elapsed();
+        println!("VS Code version check completed in {}", units::display_duration(version_time));
+
+        // Extension install from a local VSIX, if configured
+        let install_extension_time = match &self.config.vscode.vsix {
+            Some(vsix) => {
+                if !Path::new(vsix).exists() {
+                    return Err(format!("VSIX file '{}' does not exist", vsix));
+                }
+                let install_start = Instant::now();
+                self.run_code(&["--install-extension", vsix, "--force"])?;
+                let install_time = install_start.elapsed();
+                println!("VS Code extension install completed in {}", units::display_duration(install_time));
+                Some(install_time)
+            }
+            None => None,
+        };
+
+        // Extension scan
+        let list_start = Instant::now();
+        self.run_code(&["--list-extensions", "--show-versions"])?;
+        let list_extensions_time = list_start.elapsed();
+        println!("VS Code extension listing completed in {}", units::display_duration(list_extensions_time));
+
+        let ready_time = if has_display() {
+            let ready_time = self.time_to_ready()?;
+            println!("VS Code ready with extension host in {}", units::display_duration(ready_time));
+            Some(ready_time)
+        } else {
+            println!("No display available, skipping the VS Code time to ready");
+            None
+        };
+
+        Ok(VsCodeResults {
+            version_time,
+            list_extensions_time,
+            install_extension_time,
+            ready_time,
+        })
+    }
+
+    /// Launches a window on an empty workspace and polls `code --status` until
+    /// it lists an extension host, then closes that instance again.
+    fn time_to_ready(&self) -> Result<Duration, String> {
+        fs::create_dir_all(WORKSPACE_DIR)
+            .map_err(|e| format!("Failed to create directory '{}': {}", WORKSPACE_DIR, e))?;
+        let timeout = Duration::from_secs(self.config.vscode.ready_timeout_secs);
+
+        let start = Instant::now();
+        let _window = process::spawn(
+            Command::new("code")
+                .args(["--user-data-dir", USER_DATA_DIR, "--extensions-dir", EXTENSIONS_DIR])
+                .args(["--new-window", "--wait", WORKSPACE_DIR])
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null()),
+        )
+        .map_err(|e| format!("Failed to launch VS Code: {}", e))?;
+
+        let mut status = String::new();
+        let ready_time = loop {
+            if process::cancelled() {
+                return Err("VS Code time to ready was cancelled".to_string());
+            }
+            if let Ok(output) = self.code_output(&["--status"]) {
+                status = String::from_utf8_lossy(&output.stdout).into_owned();
+                if has_extension_host(&status) {
+                    break start.elapsed();
+                }
+            }
+            if start.elapsed() >= timeout {
+                close_instance(&status);
+                return Err(format!(
+                    "VS Code did not start an extension host within {} s",
+                    timeout.as_secs()
+                ));
+            }
+            std::thread::sleep(READY_POLL);
+        };
+
+        // The window runs detached from the launcher, so it is closed by its pids
+        close_instance(&status);
+        Ok(ready_time)
+    }
+
+    fn run_code(&self, args: &[&str]) -> Result<(), String> {
+        self.code_output(args).map(|_| ())
+    }
+
+    fn code_output(&self, args: &[&str]) -> Result<std::process::Output, String> {
+        let output = process::output(
+            Command::new("code")
+                .args(["--user-data-dir", USER_DATA_DIR, "--extensions-dir", EXTENSIONS_DIR])
+                .args(args),
+        )
+        .map_err(|e| format!("Failed to run code {}: {}", args.join(" "), e))?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).into_owned());
+        }
+
+        Ok(output)
+    }
+
+    pub fn open_branches(&self) -> Result<(), String> {
+        let git = self
+            .config
+            .git
+            .as_ref()
+            .ok_or_else(|| "Opening branches in VS Code needs [git] branches in config.toml".to_string())?;
 
         // First open VS Code in artifacts directory
-        Command::new("code")
-            .arg("artifacts")
-            .output()
+        process::output(Command::new("code").arg("artifacts"))
             .map_err(|e| format!("Failed to open VS Code: {}", e))?;
 
         // Change to artifacts directory
@@ -39,29 +216,31 @@ impl VsCodeOperations {
         }
 
         // For each branch in config
-        for branch in &config.git.branches {
+        for branch in &git.branches {
             // Checkout branch
-            Command::new("git")
-                .current_dir("artifacts")
-                .args(&["checkout", branch])
-                .output()
-                .map_err(|e| format!("Failed to checkout branch {}: {}", branch, e))?;
+            process::output(
+                Command::new("git")
+                    .current_dir("artifacts")
+                    .args(["checkout", branch]),
+            )
+            .map_err(|e| format!("Failed to checkout branch {}: {}", branch, e))?;
 
             // Get list of .txt files
             let files = fs::read_dir("artifacts")
                 .map_err(|e| format!("Failed to read artifacts directory: {}", e))?
                 .filter_map(|entry| entry.ok())
-                .filter(|entry| entry.path().extension().map_or(false, |ext| ext == "txt"))
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "txt"))
                 .take(3) // Only take first 3 files
                 .collect::<Vec<_>>();
 
             // Open first 3 files in VS Code
             for file in files {
-                Command::new("code")
-                    .current_dir("artifacts")
-                    .arg(file.path())
-                    .output()
-                    .map_err(|e| format!("Failed to open file in VS Code: {}", e))?;
+                process::output(
+                    Command::new("code")
+                        .current_dir("artifacts")
+                        .arg(file.path()),
+                )
+                .map_err(|e| format!("Failed to open file in VS Code: {}", e))?;
             }
         }
 
@@ -69,3 +248,77 @@ impl VsCodeOperations {
         Ok(())
     }
 }
+
+/// Whether a window can be opened; always on Windows and macOS.
+fn has_display() -> bool {
+    !cfg!(target_os = "linux") || ["DISPLAY", "WAYLAND_DISPLAY"].iter().any(|var| std::env::var_os(var).is_some())
+}
+
+/// Whether `code --status` lists an extension host, named "extensionHost" or
+/// "extension-host" depending on the version.
+fn has_extension_host(status: &str) -> bool {
+    let status = status.to_ascii_lowercase();
+    status.contains("extensionhost") || status.contains("extension-host")
+}
+
+/// Pids in the process table of `code --status`, whose rows are
+/// "CPU %  Mem MB  PID  Process".
+fn status_pids(status: &str) -> Vec<u32> {
+    status
+        .lines()
+        .skip_while(|line| !line.contains("PID"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            columns.next()?.parse::<f64>().ok()?;
+            columns.next()?.parse::<f64>().ok()?;
+            columns.next()?.parse().ok()
+        })
+        .collect()
+}
+
+fn close_instance(status: &str) {
+    for pid in status_pids(status) {
+        process::kill_tree(pid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS: &str = "Version:          Code 1.85.1 (0ee08df0cf4527e40edc9aa28f4b5bd38bbff2b2, 2023-12-13T09:49:37.021Z)
+OS Version:       Linux x64 6.5.0
+CPUs:             Intel(R) Core(TM) i7-1185G7 @ 3.00GHz (8 x 3000)
+Memory (System):  31.09GB (20.52GB free)
+
+CPU %\tMem MB\t   PID\tProcess
+    0\t   102\t 12345\tcode main
+    0\t    61\t 12350\t   gpu-process
+    1\t   117\t 12400\t   window [1] (Welcome - vscode-workspace - Visual Studio Code)
+    0\t    88\t 12420\t   extensionHost [1]
+
+Workspace Stats:
+|  Window (Welcome - vscode-workspace - Visual Studio Code)
+";
+
+    #[test]
+    fn detects_the_extension_host() {
+        assert!(has_extension_host(STATUS));
+        assert!(has_extension_host("    0\t 90\t 1\t   extension-host [1]"));
+        assert!(!has_extension_host(&STATUS.replace("extensionHost [1]", "shared-process")));
+    }
+
+    #[test]
+    fn reads_the_pids_of_the_process_table() {
+        assert_eq!(status_pids(STATUS), [12345, 12350, 12400, 12420]);
+        assert!(status_pids("Version: Code 1.85.1").is_empty());
+    }
+
+    #[test]
+    fn git_is_optional_in_headless_mode() {
+        let config: Config = toml::from_str("[vscode]\nheadless = true").unwrap();
+        assert!(config.git.is_none());
+        assert_eq!(config.vscode.ready_timeout_secs, 60);
+    }
+}